@@ -15,7 +15,7 @@ use cortex_m_log::{
 #[entry]
 fn main() -> ! {
     let cp = cortex_m::Peripherals::take().unwrap();
-    let dp = pac::Peripherals::take().unwrap();
+    let mut dp = pac::Peripherals::take().unwrap();
     let mut log = InterruptSyncItm::new(Itm::new(cp.ITM));
 
     // Constrain and Freeze power
@@ -33,9 +33,16 @@ fn main() -> ! {
     let scl = gpiob.pb8.into_alternate_af4().set_open_drain();
     let sda = gpiob.pb9.into_alternate_af4().set_open_drain();
 
-    let mut i2c =
-        dp.I2C1
-            .i2c((scl, sda), 100.khz(), ccdr.peripheral.I2C1, &ccdr.clocks);
+    let mut i2c = dp
+        .I2C1
+        .i2c(
+            (scl, sda),
+            100.khz(),
+            ccdr.peripheral.I2C1,
+            &ccdr.clocks,
+            &mut dp.SYSCFG,
+        )
+        .unwrap();
 
     // Echo what is received on the I2C at register 0x60
     let mut buf = [0x60];
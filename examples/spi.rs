@@ -47,13 +47,16 @@ fn main() -> ! {
     println!(log, "");
 
     // Initialise the SPI peripheral.
-    let mut spi = dp.SPI3.spi(
-        (sck, miso, mosi),
-        spi::MODE_0,
-        3.mhz(),
-        ccdr.peripheral.SPI3,
-        &ccdr.clocks,
-    );
+    let mut spi = dp
+        .SPI3
+        .spi(
+            (sck, miso, mosi),
+            spi::MODE_0,
+            3.mhz(),
+            ccdr.peripheral.SPI3,
+            &ccdr.clocks,
+        )
+        .unwrap();
 
     // Write fixed data
     spi.write(&[0x11u8, 0x22, 0x33]).unwrap();
@@ -6,13 +6,7 @@ extern crate panic_itm;
 
 use cortex_m_rt::entry;
 
-use stm32h7xx_hal::{
-    adc,
-    delay::Delay,
-    pac,
-    prelude::*,
-    signature::{TS_CAL_110, TS_CAL_30},
-};
+use stm32h7xx_hal::{adc, delay::Delay, pac, prelude::*};
 
 use cortex_m_log::println;
 use cortex_m_log::{
@@ -50,30 +44,19 @@ fn main() -> ! {
         adc::Adc::adc3(dp.ADC3, &mut delay, ccdr.peripheral.ADC3, &ccdr.clocks);
     adc3.set_resolution(adc::Resolution::SIXTEENBIT);
 
-    // Setup Temperature Sensor on the disabled ADC
+    // Setup Temperature Sensor and Vrefint on the disabled ADC
     let mut channel = adc::Temperature::new();
     channel.enable(&adc3);
+    let mut vrefint = adc::Vrefint::new();
+    vrefint.enable(&adc3);
     delay.delay_us(25_u16);
     let mut adc3 = adc3.enable();
 
-    let vdda = 2.500; // Volts
-
     loop {
-        let word: u32 =
-            adc3.read(&mut channel).expect("Temperature read failed.");
-
-        // Average slope
-        let cal = (110.0 - 30.0)
-            / (TS_CAL_110::get().read() - TS_CAL_30::get().read()) as f32;
-        // Calibration values are measured at VDDA = 3.3 V ± 10 mV
-        let word_3v3 = word as f32 * vdda / 3.3;
-        // Linear interpolation
-        let temperature =
-            cal * (word_3v3 - TS_CAL_30::get().read() as f32) + 30.0;
+        // Calibrated conversions
+        let vdda = adc3.read_vdda(&mut vrefint).unwrap();
+        let temperature = adc3.read_temperature(&mut channel, vdda);
 
-        println!(
-            log,
-            "ADC reading: {}, Temperature: {:.1} °C", word, temperature
-        );
+        println!(log, "VDDA: {} mV, Temperature: {:.1} °C", vdda, temperature);
     }
 }
@@ -1,12 +1,210 @@
 //! Analog to Digital Converter (ADC)
+//!
+//! # Sequences
+//!
+//! Up to 16 channels can be converted in a single scan with a
+//! [`Sequence`]. Each channel has its own sample time.
+//!
+//! ```
+//! let sequence = adc::Sequence::new()
+//!     .add(&channel_a, AdcSampleTime::T_64)
+//!     .add(&channel_b, AdcSampleTime::T_8);
+//!
+//! let mut results = [0; 2];
+//! adc1.read_sequence(&sequence, &mut results);
+//! ```
+//!
+//! # Continuous Conversions
+//!
+//! A sequence can be converted continuously, or discontinuously, with
+//! the results transferred to a buffer by a circular DMA transfer. Each
+//! half of the buffer can be processed whilst the other is being
+//! filled.
+//!
+//! ```
+//! let mut transfer = adc1.start_circular(
+//!     &sequence,
+//!     adc::ConversionMode::Continuous,
+//!     dp.DMA1,
+//!     ccdr.peripheral.DMA1,
+//!     0,
+//!     &dp.DMAMUX1,
+//!     unsafe { &mut BUFFER },
+//! );
+//!
+//! // In the DMA1_STR0 interrupt
+//! if let Ok(half) = transfer.poll() {
+//!     process(transfer.half(half));
+//! }
+//! ```
+//!
+//! The buffer must not be in the DTCM, which is not accessible to DMA1
+//! and DMA2. If the data cache is enabled, the buffer should be in a
+//! non-cacheable region or invalidated before it is read.
+//!
+//! # External Triggers
+//!
+//! Regular conversions can be started by a hardware trigger instead of
+//! by software, so that the samples are evenly spaced. For example, to
+//! convert a sequence at 8kHz using the TRGO output of TIM2:
+//!
+//! ```
+//! let mut timer = dp.TIM2.timer(8.khz(), ccdr.peripheral.TIM2, &ccdr.clocks);
+//! timer.trigger_on_update();
+//!
+//! adc1.set_external_trigger(adc::ExternalTrigger::Tim2Trgo, adc::TriggerEdge::Rising);
+//! let mut transfer = adc1.start_circular(
+//!     &sequence,
+//!     adc::ConversionMode::Discontinuous(1),
+//!     ...
+//! );
+//! ```
+//!
+//! Once a trigger is set, each conversion or sequence started by this
+//! driver waits for the trigger. In continuous mode, the first trigger
+//! starts the conversions.
+//!
+//! # Injected Conversions
+//!
+//! An [`InjectedSequence`] of up to 4 channels can be converted at a
+//! precise instant, pre-empting any regular conversions. The results
+//! are held in separate registers until they are read.
+//!
+//! ```
+//! let injected = adc::InjectedSequence::new()
+//!     .add(&current_a, AdcSampleTime::T_2)
+//!     .add_with_offset(&current_b, AdcSampleTime::T_2, 32768)
+//!     .trigger(adc::InjectedTrigger::Tim1Trgo, adc::TriggerEdge::Rising);
+//!
+//! adc1.set_injected_sequence(&injected);
+//! adc1.listen(adc::Event::InjectedEndOfSequence);
+//! adc1.start_injected();
+//!
+//! // In the ADC interrupt
+//! let mut currents = [0; 2];
+//! if adc1.read_injected(&mut currents).is_ok() { ... }
+//! ```
+//!
+//! # Differential Inputs
+//!
+//! A pair of pins can be converted differentially. The pair is
+//! type-checked, only the INPx/INNx pairs of each ADC are accepted. The
+//! differential mode must be set before the ADC is enabled, and
+//! [`calibrate`](struct.Adc.html#method.calibrate) calibrates both
+//! single-ended and differential channels.
+//!
+//! ```
+//! let mut pair = adc::Differential::new(pa6, pa7);
+//! adc1.calibrate();
+//! adc1.set_differential(&pair);
+//! let mut adc1 = adc1.enable();
+//!
+//! let value: i32 = adc1.read_differential(&mut pair);
+//! ```
+//!
+//! # Dual Mode
+//!
+//! ADC1 and ADC2 share a kernel clock and reset, and can be initialised
+//! together with [`Adc::adc12`]. They can then convert simultaneously,
+//! or interleave their conversions of one channel to double the sample
+//! rate, as a [`DualAdc`].
+//!
+//! ```
+//! let (adc1, adc2) = adc::Adc::adc12(dp.ADC1, dp.ADC2, &mut delay, ccdr.peripheral.ADC12, &ccdr.clocks);
+//! let mut dual = adc::DualAdc::new(adc1, adc2, adc::DualMode::RegularSimultaneous);
+//!
+//! let current = adc::Sequence::new().add(&pa6, AdcSampleTime::T_16);
+//! let voltage = adc::Sequence::new().add(&pc4, AdcSampleTime::T_16);
+//!
+//! let mut results = [(0, 0); 1];
+//! dual.read_simultaneous(&current, &voltage, &mut results);
+//! ```
+//!
+//! A `DualAdc` can also start a circular DMA transfer, in which each
+//! word holds a pair of results packed in the common data register.
+//!
+//! # Oversampling
+//!
+//! The ADC can sum up to 1024 conversions in hardware, reducing the
+//! noise at the expense of the sample rate. The sum is shifted right to
+//! fit in the data register.
+//!
+//! ```
+//! // Average 16 conversions
+//! adc1.set_oversampling(adc::AdcOversampling::new(16, 4));
+//! ```
+//!
+//! # Direct Channels
+//!
+//! Channels 0 and 1 of each ADC are the fast direct inputs from the
+//! Pxy_C pads. These pads are connected to the GPIO pads of the same
+//! name by analog switches, which are opened when a [`Pa0C`], [`Pa1C`],
+//! [`Pc2C`] or [`Pc3C`] is created. If VDDA is below 2.7V, the supply
+//! of the switches should be set with [`set_analog_switch_supply`].
+//!
+//! ```
+//! let mut pa0_c = adc::Pa0C::new(&mut dp.SYSCFG);
+//! let data: u32 = adc1.read(&mut pa0_c).unwrap();
+//! ```
+//!
+//! # Calibrated Conversions
+//!
+//! ADC3 can convert its internal channels to physical units using the
+//! factory calibration in the [`signature`](crate::signature) module.
+//!
+//! ```
+//! let vdda = adc3.read_vdda(&mut vrefint).unwrap(); // mV
+//! let temperature = adc3.read_temperature(&mut sensor, vdda); // °C
+//! let vbat = adc3.read_vbat(&mut vbat, vdda); // mV
+//! ```
+//!
+//! # Offsets and Calibration
+//!
+//! Each ADC has four offset registers, which subtract an offset from the
+//! results of a channel. The results are then signed.
+//!
+//! ```
+//! adc1.set_channel_offset(adc::OffsetRegister::Ofr1, &pin, 32768, true);
+//! let value: i32 = adc1.read_signed(&mut pin);
+//! ```
+//!
+//! The calibration values can be saved and written back, for example
+//! after a wake-up from Standby, rather than calibrating again.
+//!
+//! ```
+//! let offset = adc1.read_offset_calibration_value();
+//! let linear = adc1.read_linear_calibration_values();
+//! // ...
+//! adc1.write_offset_calibration_value(offset);
+//! adc1.write_linear_calibration_values(linear);
+//! ```
+//!
+//! # Analog Watchdogs
+//!
+//! Each ADC has three analog watchdogs, which flag results outside a
+//! window without any CPU intervention. `Awd1` guards a single channel
+//! or all channels, `Awd2` and `Awd3` guard any set of channels.
+//!
+//! ```
+//! let channels = adc::WatchdogChannels::new().add(&current);
+//! adc1.enable_watchdog(adc::AnalogWatchdog::Awd1, &channels, 0, 50_000);
+//! adc1.listen(adc::Event::AnalogWatchdog(adc::AnalogWatchdog::Awd1));
+//! ```
 
 use crate::hal::adc::{Channel, OneShot};
 use crate::hal::blocking::delay::DelayUs;
 
+use core::cmp;
 use core::marker::PhantomData;
+use core::ops::Deref;
+use cortex_m::interrupt;
+use void::Void;
 
 use crate::stm32;
-use crate::stm32::{ADC1, ADC2, ADC3, ADC3_COMMON};
+use crate::stm32::dma1;
+use crate::stm32::{
+    ADC1, ADC12_COMMON, ADC2, ADC3, ADC3_COMMON, DMA1, DMA2, DMAMUX1, SYSCFG,
+};
 
 use crate::delay::Delay;
 use crate::gpio::gpioa::{PA0, PA1, PA2, PA3, PA4, PA5, PA6, PA7};
@@ -18,6 +216,7 @@ use crate::gpio::gpiof::{
 use crate::gpio::gpioh::{PH2, PH3, PH4, PH5};
 use crate::gpio::Analog;
 use crate::rcc::{rec, CoreClocks, ResetEnable};
+use crate::signature::{TS_CAL_110, TS_CAL_30, VDDA_CALIB, VREFIN_CAL};
 
 #[cfg(not(feature = "revision_v"))]
 const ADC_KER_CK_MAX: u32 = 36_000_000;
@@ -42,6 +241,65 @@ impl NumberOfBits for Resolution {
     }
 }
 
+/// Hardware trigger for regular conversions
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum ExternalTrigger {
+    /// TIM1 capture/compare 1
+    Tim1Cc1 = 0,
+    /// TIM1 capture/compare 2
+    Tim1Cc2 = 1,
+    /// TIM1 capture/compare 3
+    Tim1Cc3 = 2,
+    /// TIM2 capture/compare 2
+    Tim2Cc2 = 3,
+    /// TIM3 TRGO
+    Tim3Trgo = 4,
+    /// TIM4 capture/compare 4
+    Tim4Cc4 = 5,
+    /// EXTI line 11
+    Exti11 = 6,
+    /// TIM8 TRGO
+    Tim8Trgo = 7,
+    /// TIM8 TRGO2
+    Tim8Trgo2 = 8,
+    /// TIM1 TRGO
+    Tim1Trgo = 9,
+    /// TIM1 TRGO2
+    Tim1Trgo2 = 10,
+    /// TIM2 TRGO
+    Tim2Trgo = 11,
+    /// TIM4 TRGO
+    Tim4Trgo = 12,
+    /// TIM6 TRGO
+    Tim6Trgo = 13,
+    /// TIM15 TRGO
+    Tim15Trgo = 14,
+    /// TIM3 capture/compare 4
+    Tim3Cc4 = 15,
+    /// HRTIM ADC trigger 1
+    HrtimAdcTrg1 = 16,
+    /// HRTIM ADC trigger 3
+    HrtimAdcTrg3 = 17,
+    /// LPTIM1 output
+    Lptim1Out = 18,
+    /// LPTIM2 output
+    Lptim2Out = 19,
+    /// LPTIM3 output
+    Lptim3Out = 20,
+}
+
+/// Edge of an [`ExternalTrigger`] that starts a conversion
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TriggerEdge {
+    /// Rising edge
+    Rising,
+    /// Falling edge
+    Falling,
+    /// Both rising and falling edges
+    Both,
+}
+
 /// Enabled ADC (type state)
 pub struct Enabled;
 /// Disabled ADC (type state)
@@ -56,6 +314,7 @@ pub struct Adc<ADC, ED> {
     sample_time: AdcSampleTime,
     resolution: Resolution,
     lshift: AdcLshift,
+    oversampling: AdcOversampling,
     _enabled: PhantomData<ED>,
 }
 
@@ -131,10 +390,111 @@ impl AdcLshift {
     }
 }
 
+/// Mode of regular oversampling when it is interrupted by an injected
+/// conversion
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OversamplingMode {
+    /// The regular oversampling continues, keeping the partial sum
+    Continued,
+    /// The regular oversampling restarts from the beginning
+    Resumed,
+}
+
+/// ADC hardware oversampling
+///
+/// Each result is the sum of `ratio` conversions, shifted right by
+/// `rshift` bits. The ratio is 1..=1024 and the right shift is 0..=10.
+///
+/// Refer to RM0433 Rev 7 - Chapter 25.4.31
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdcOversampling {
+    ratio: u16,
+    rshift: u8,
+    regular: bool,
+    injected: bool,
+    triggered: bool,
+    mode: OversamplingMode,
+}
+
+impl AdcOversampling {
+    /// Oversample regular conversions by `ratio`, shifting the sum right
+    /// by `rshift` bits
+    pub fn new(ratio: u16, rshift: u8) -> Self {
+        if !(1..=1024).contains(&ratio) {
+            panic!("The oversampling ratio must be in range of 1..=1024");
+        }
+        if rshift > 10 {
+            panic!("The oversampling right shift must be in range of 0..=10");
+        }
+
+        AdcOversampling {
+            ratio,
+            rshift,
+            regular: true,
+            injected: false,
+            triggered: false,
+            mode: OversamplingMode::Continued,
+        }
+    }
+
+    /// No oversampling
+    pub fn default() -> Self {
+        AdcOversampling {
+            regular: false,
+            ..Self::new(1, 0)
+        }
+    }
+
+    /// Oversample regular conversions
+    pub fn regular(mut self, enable: bool) -> Self {
+        self.regular = enable;
+        self
+    }
+
+    /// Oversample injected conversions
+    pub fn injected(mut self, enable: bool) -> Self {
+        self.injected = enable;
+        self
+    }
+
+    /// Start each regular conversion of the oversampling on a new
+    /// trigger, rather than starting them all on a single trigger
+    pub fn triggered(mut self, enable: bool) -> Self {
+        self.triggered = enable;
+        self
+    }
+
+    /// Set the mode of regular oversampling when it is interrupted by
+    /// an injected conversion
+    pub fn mode(mut self, mode: OversamplingMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns the oversampling ratio
+    pub fn ratio(self) -> u16 {
+        self.ratio
+    }
+
+    /// Returns the right shift
+    pub fn rshift(self) -> u8 {
+        self.rshift
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct AdcCalOffset(u16);
 
 impl AdcCalOffset {
+    /// A previously read offset calibration value
+    pub fn new(value: u16) -> Self {
+        if value >= 1 << 11 {
+            panic!("CALFACT must be in range of 0..2048");
+        }
+
+        AdcCalOffset(value)
+    }
+
     pub fn value(self) -> u16 {
         self.0
     }
@@ -144,11 +504,30 @@ impl AdcCalOffset {
 pub struct AdcCalLinear([u32; 6]);
 
 impl AdcCalLinear {
+    /// Previously read linear calibration values
+    pub fn new(value: [u32; 6]) -> Self {
+        AdcCalLinear(value)
+    }
+
     pub fn value(self) -> [u32; 6] {
         self.0
     }
 }
 
+/// ADC offset registers, each subtracting an offset from the results
+/// of one channel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OffsetRegister {
+    /// OFR1
+    Ofr1,
+    /// OFR2
+    Ofr2,
+    /// OFR3
+    Ofr3,
+    /// OFR4
+    Ofr4,
+}
+
 macro_rules! adc_pins {
     ($ADC:ident, $($input:ty => $chan:expr),+ $(,)*) => {
         $(
@@ -201,12 +580,92 @@ pub struct Vbat;
 /// Internal temperature sensor
 pub struct Temperature;
 
-// Not implementing Pxy_C adc pins
-// Just implmenting INPx pins (INNx defaulting to V_ref-)
+/// Supply of the analog switches between the Pxy and Pxy_C pads
+///
+/// Refer to RM0433 Rev 7 - Chapter 12.3.1
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AnalogSwitchSupply {
+    /// VDDA, when VDDA is at least 2.7V
+    Vdda,
+    /// VDDA with the booster enabled (BOOSTE), when both VDDA and VDD are
+    /// below 2.7V
+    VddaBoosted,
+    /// VDD (BOOSTVDDSEL), when VDDA is below 2.7V but VDD is at least
+    /// 2.7V
+    Vdd,
+}
+
+/// Set the supply of the analog switches, to reduce their distortion
+/// when VDDA is low
+pub fn set_analog_switch_supply(
+    syscfg: &mut SYSCFG,
+    supply: AnalogSwitchSupply,
+) {
+    let (booste, boostvddsel) = match supply {
+        AnalogSwitchSupply::Vdda => (false, false),
+        AnalogSwitchSupply::VddaBoosted => (true, false),
+        AnalogSwitchSupply::Vdd => (false, true),
+    };
+
+    // BOOSTVDDSEL is bit 9 of PMCR, but is not in every device's PAC.
+    // PMCR is shared with other peripherals
+    interrupt::free(|_| {
+        syscfg.pmcr.modify(|r, w| unsafe {
+            w.bits((r.bits() & !(1 << 9)) | (u32::from(boostvddsel) << 9))
+                .booste()
+                .bit(booste)
+        })
+    });
+}
+
+macro_rules! adc_direct_channels {
+    ($($Pxy_C:ident: ($so:ident, $name:expr)),+ $(,)*) => {
+        $(
+            #[doc = "Direct input from the "]
+            #[doc = $name]
+            #[doc = " pad"]
+            pub struct $Pxy_C {
+                _private: (),
+            }
+
+            impl $Pxy_C {
+                /// Open the analog switch between this pad and the GPIO
+                /// pad of the same name, so that each can be used
+                /// separately
+                pub fn new(syscfg: &mut SYSCFG) -> Self {
+                    // PMCR is shared with other peripherals
+                    interrupt::free(|_| {
+                        syscfg.pmcr.modify(|_, w| w.$so().set_bit())
+                    });
+
+                    $Pxy_C { _private: () }
+                }
+
+                /// Close the analog switch again
+                pub fn free(self, syscfg: &mut SYSCFG) {
+                    interrupt::free(|_| {
+                        syscfg.pmcr.modify(|_, w| w.$so().clear_bit())
+                    });
+                }
+            }
+        )+
+    };
+}
+
+adc_direct_channels!(
+    Pa0C: (pa0so, "PA0_C"),
+    Pa1C: (pa1so, "PA1_C"),
+    Pc2C: (pc2so, "PC2_C"),
+    Pc3C: (pc3so, "PC3_C"),
+);
+
+// Single-ended INPx pins (INNx defaulting to V_ref-), differential pairs
+// are below
 //
 // Refer to DS12110 Rev 7 - Chapter 5 (Table 9)
 adc_pins!(ADC1,
-          // 0, 1 are Pxy_C pins
+          Pa0C => 0,
+          Pa1C => 1,
           PF11<Analog> => 2,
           PA6<Analog> => 3,
           PC4<Analog> => 4,
@@ -228,7 +687,8 @@ adc_pins!(ADC1,
 );
 
 adc_pins!(ADC2,
-          // 0, 1 are Pxy_C pins
+          Pa0C => 0,
+          Pa1C => 1,
           PF13<Analog> => 2,
           PA6<Analog> => 3,
           PC4<Analog> => 4,
@@ -249,7 +709,8 @@ adc_pins!(ADC2,
 );
 
 adc_pins!(ADC3,
-          // 0, 1 are Pxy_C pins
+          Pc2C => 0,
+          Pc3C => 1,
           PF9<Analog> => 2,
           PF7<Analog> => 3,
           PF5<Analog> => 4,
@@ -272,6 +733,540 @@ adc_internal!(
           Vrefint => (19, vrefen)
 );
 
+/// A differential input, converting the voltage of the `positive` pin
+/// (INPx) relative to the `negative` pin (INNx)
+///
+/// Only valid pairs of pins implement `Channel`.
+pub struct Differential<P, N> {
+    positive: P,
+    negative: N,
+}
+
+impl<P, N> Differential<P, N> {
+    /// Pair a `positive` and a `negative` pin
+    pub fn new(positive: P, negative: N) -> Self {
+        Differential { positive, negative }
+    }
+
+    /// Releases the pins
+    pub fn free(self) -> (P, N) {
+        (self.positive, self.negative)
+    }
+}
+
+macro_rules! adc_differential_pins {
+    ($ADC:ident, $($p:ty, $n:ty => $chan:expr),+ $(,)*) => {
+        $(
+            impl Channel<$ADC> for Differential<$p, $n> {
+                type ID = u8;
+
+                fn channel() -> u8 {
+                    $chan
+                }
+            }
+        )+
+    };
+}
+
+// Differential pairs (INPx, INNx). The channel is that of INPx
+//
+// Refer to DS12110 Rev 7 - Chapter 5 (Table 9)
+adc_differential_pins!(ADC1,
+          Pa1C, Pa0C => 1,
+          PF11<Analog>, PF12<Analog> => 2,
+          PA6<Analog>, PA7<Analog> => 3,
+          PC4<Analog>, PC5<Analog> => 4,
+          PB1<Analog>, PB0<Analog> => 5,
+          PC0<Analog>, PC1<Analog> => 10,
+          PC1<Analog>, PC2<Analog> => 11,
+          PC2<Analog>, PC3<Analog> => 12,
+          PA0<Analog>, PA1<Analog> => 16,
+          PA4<Analog>, PA5<Analog> => 18,
+);
+
+adc_differential_pins!(ADC2,
+          Pa1C, Pa0C => 1,
+          PF13<Analog>, PF14<Analog> => 2,
+          PA6<Analog>, PA7<Analog> => 3,
+          PC4<Analog>, PC5<Analog> => 4,
+          PB1<Analog>, PB0<Analog> => 5,
+          PC0<Analog>, PC1<Analog> => 10,
+          PC1<Analog>, PC2<Analog> => 11,
+          PC2<Analog>, PC3<Analog> => 12,
+          PA4<Analog>, PA5<Analog> => 18,
+);
+
+adc_differential_pins!(ADC3,
+          Pc3C, Pc2C => 1,
+          PF9<Analog>, PF10<Analog> => 2,
+          PF7<Analog>, PF8<Analog> => 3,
+          PF5<Analog>, PF6<Analog> => 4,
+          PF3<Analog>, PF4<Analog> => 5,
+          PC0<Analog>, PC1<Analog> => 10,
+          PC1<Analog>, PC2<Analog> => 11,
+          PC2<Analog>, PH2<Analog> => 12,
+          PH2<Analog>, PH3<Analog> => 13,
+          PH3<Analog>, PH4<Analog> => 14,
+          PH4<Analog>, PH5<Analog> => 15,
+);
+
+/// A regular sequence of up to 16 channels
+///
+/// Each rank of the sequence is a channel, with its own sample time.
+/// The same channel may appear in several ranks, but each channel has a
+/// single sample time, which is the one given last.
+pub struct Sequence<ADC> {
+    ranks: [(u8, AdcSampleTime); 16],
+    len: usize,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC> Sequence<ADC> {
+    /// Create an empty sequence
+    pub fn new() -> Self {
+        Sequence {
+            ranks: [(0, AdcSampleTime::default()); 16],
+            len: 0,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Append the channel for `pin` to the sequence, sampled for
+    /// `sample_time`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sequence already has 16 ranks
+    pub fn add<PIN>(mut self, _pin: &PIN, sample_time: AdcSampleTime) -> Self
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        assert!(self.len < 16, "A sequence has at most 16 ranks");
+
+        self.ranks[self.len] = (PIN::channel(), sample_time);
+        self.len += 1;
+        self
+    }
+
+    /// Returns the number of ranks in the sequence
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the sequence has no ranks
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<ADC> Default for Sequence<ADC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hardware trigger for injected conversions
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum InjectedTrigger {
+    /// TIM1 TRGO
+    Tim1Trgo = 0,
+    /// TIM1 capture/compare 4
+    Tim1Cc4 = 1,
+    /// TIM2 TRGO
+    Tim2Trgo = 2,
+    /// TIM2 capture/compare 1
+    Tim2Cc1 = 3,
+    /// TIM3 capture/compare 4
+    Tim3Cc4 = 4,
+    /// TIM4 TRGO
+    Tim4Trgo = 5,
+    /// EXTI line 15
+    Exti15 = 6,
+    /// TIM8 capture/compare 4
+    Tim8Cc4 = 7,
+    /// TIM1 TRGO2
+    Tim1Trgo2 = 8,
+    /// TIM8 TRGO
+    Tim8Trgo = 9,
+    /// TIM8 TRGO2
+    Tim8Trgo2 = 10,
+    /// TIM3 capture/compare 3
+    Tim3Cc3 = 11,
+    /// TIM3 TRGO
+    Tim3Trgo = 12,
+    /// TIM3 capture/compare 1
+    Tim3Cc1 = 13,
+    /// TIM6 TRGO
+    Tim6Trgo = 14,
+    /// TIM15 TRGO
+    Tim15Trgo = 15,
+    /// HRTIM ADC trigger 2
+    HrtimAdcTrg2 = 16,
+    /// HRTIM ADC trigger 4
+    HrtimAdcTrg4 = 17,
+    /// LPTIM1 output
+    Lptim1Out = 18,
+    /// LPTIM2 output
+    Lptim2Out = 19,
+    /// LPTIM3 output
+    Lptim3Out = 20,
+}
+
+/// An injected sequence of up to 4 channels
+///
+/// Each rank may have an offset, which is subtracted from its
+/// results. The offset of the first rank is held in `OFR1`, the second
+/// in `OFR2` and so on. The offset applies to the channel, so it also
+/// applies to regular conversions of that channel.
+///
+/// Programming the sequence writes all four offset registers, clearing
+/// those without an offset. This replaces any offsets set with
+/// [`set_channel_offset`](Adc::set_channel_offset), which should be
+/// set afterwards instead.
+pub struct InjectedSequence<ADC> {
+    ranks: [(u8, AdcSampleTime, u32); 4],
+    len: usize,
+    trigger: Option<(InjectedTrigger, TriggerEdge)>,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC> InjectedSequence<ADC> {
+    /// Create an empty injected sequence, started by software
+    pub fn new() -> Self {
+        InjectedSequence {
+            ranks: [(0, AdcSampleTime::default(), 0); 4],
+            len: 0,
+            trigger: None,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Append the channel for `pin` to the sequence, sampled for
+    /// `sample_time`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sequence already has 4 ranks
+    pub fn add<PIN>(self, pin: &PIN, sample_time: AdcSampleTime) -> Self
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        self.add_with_offset(pin, sample_time, 0)
+    }
+
+    /// Append the channel for `pin` to the sequence, sampled for
+    /// `sample_time`. `offset` is subtracted from each result, which
+    /// may then be negative.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sequence already has 4 ranks, or if `offset` does
+    /// not fit in 26 bits
+    pub fn add_with_offset<PIN>(
+        mut self,
+        _pin: &PIN,
+        sample_time: AdcSampleTime,
+        offset: u32,
+    ) -> Self
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        assert!(self.len < 4, "An injected sequence has at most 4 ranks");
+        assert!(offset < (1 << 26));
+
+        self.ranks[self.len] = (PIN::channel(), sample_time, offset);
+        self.len += 1;
+        self
+    }
+
+    /// Start the sequence on `edge` of `trigger`, instead of by software
+    pub fn trigger(
+        mut self,
+        trigger: InjectedTrigger,
+        edge: TriggerEdge,
+    ) -> Self {
+        self.trigger = Some((trigger, edge));
+        self
+    }
+
+    /// Returns the number of ranks in the sequence
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the sequence has no ranks
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<ADC> Default for InjectedSequence<ADC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ADC interrupt events
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    /// End of the injected sequence (JEOS)
+    InjectedEndOfSequence,
+    /// A result is outside the thresholds of an analog watchdog
+    AnalogWatchdog(AnalogWatchdog),
+}
+
+/// Analog watchdogs
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AnalogWatchdog {
+    /// Analog watchdog 1, guarding a single channel or all channels
+    Awd1,
+    /// Analog watchdog 2, guarding a set of channels
+    Awd2,
+    /// Analog watchdog 3, guarding a set of channels
+    Awd3,
+}
+
+/// The channels guarded by an analog watchdog
+pub struct WatchdogChannels<ADC> {
+    mask: u32,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC> WatchdogChannels<ADC> {
+    /// No channels
+    pub fn new() -> Self {
+        WatchdogChannels {
+            mask: 0,
+            _adc: PhantomData,
+        }
+    }
+
+    /// All channels
+    pub fn all() -> Self {
+        WatchdogChannels {
+            mask: 0xF_FFFF,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Add the channel for `pin`
+    pub fn add<PIN>(mut self, _pin: &PIN) -> Self
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        self.mask |= 1 << PIN::channel();
+        self
+    }
+}
+
+impl<ADC> Default for WatchdogChannels<ADC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Conversion mode for [`Adc::start_circular`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ConversionMode {
+    /// The sequence is converted repeatedly, as soon as the previous
+    /// conversion finishes
+    Continuous,
+    /// Each trigger converts the next `n` ranks of the sequence, where
+    /// `n` is 1 to 8
+    Discontinuous(u8),
+}
+
+/// One half of the buffer of a [`CircularTransfer`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Half {
+    /// The first half
+    First,
+    /// The second half
+    Second,
+}
+
+/// Errors from a [`CircularTransfer`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CircularError {
+    /// A result was overwritten before it was transferred (OVR)
+    Overrun,
+    /// DMA transfer error
+    Transfer,
+}
+
+/// DMA1 or DMA2, for circular transfers from an ADC
+pub trait DmaPeripheral: Deref<Target = dma1::RegisterBlock> {
+    /// Reset and enable control for this DMA
+    type Rec: ResetEnable;
+
+    /// The first DMAMUX1 channel of this DMA
+    #[doc(hidden)]
+    const DMAMUX_CHANNEL: usize;
+}
+
+impl DmaPeripheral for DMA1 {
+    type Rec = rec::Dma1;
+    const DMAMUX_CHANNEL: usize = 0;
+}
+
+impl DmaPeripheral for DMA2 {
+    type Rec = rec::Dma2;
+    const DMAMUX_CHANNEL: usize = 8;
+}
+
+/// Conversions of a sequence, transferred to a buffer by a circular
+/// DMA transfer
+pub struct CircularTransfer<ADC, DMA: DmaPeripheral> {
+    adc: Adc<ADC, Enabled>,
+    dma: CircularDma<DMA>,
+    pcsel: u32,
+}
+
+/// A circular DMA transfer from an ADC data register
+struct CircularDma<DMA: DmaPeripheral> {
+    dma: DMA,
+    prec: DMA::Rec,
+    stream: usize,
+    buffer: &'static mut [u32],
+}
+
+impl<DMA> CircularDma<DMA>
+where
+    DMA: DmaPeripheral,
+{
+    /// Enable the DMA, and start transferring words from `peripheral` to
+    /// `buffer` on each DMAMUX1 `request`
+    fn start(
+        dma: DMA,
+        prec: DMA::Rec,
+        stream: usize,
+        dmamux: &DMAMUX1,
+        request: u8,
+        peripheral: u32,
+        buffer: &'static mut [u32],
+    ) -> Self {
+        assert!(stream < 8);
+        assert!(buffer.len() & 1 == 0 && buffer.len() <= 65535);
+
+        let prec = prec.enable();
+
+        // DMAMUX1 channels 0-7 are DMA1, 8-15 are DMA2
+        let channel = DMA::DMAMUX_CHANNEL + stream;
+        dmamux.ccr[channel]
+            .modify(|_, w| unsafe { w.dmareq_id().bits(request) });
+
+        // Refer to RM0433 Rev 7 - Chapter 15.3.19
+        let st = &dma.st[stream];
+        st.cr.modify(|_, w| w.en().clear_bit());
+        while st.cr.read().en().bit_is_set() {}
+        clear_dma_flags(&dma, stream);
+
+        st.par.write(|w| w.pa().bits(peripheral));
+        st.m0ar.write(|w| w.m0a().bits(buffer.as_ptr() as u32));
+        st.ndtr.write(|w| w.ndt().bits(buffer.len() as u16));
+        st.fcr.write(|w| w.dmdis().clear_bit());
+        st.cr.write(|w| {
+            w.dir()
+                .peripheral_to_memory()
+                .circ()
+                .set_bit()
+                .pinc()
+                .clear_bit()
+                .minc()
+                .set_bit()
+                .psize()
+                .bits32()
+                .msize()
+                .bits32()
+                .pl()
+                .high()
+                .htie()
+                .set_bit()
+                .tcie()
+                .set_bit()
+                .teie()
+                .set_bit()
+        });
+        st.cr.modify(|_, w| w.en().set_bit());
+
+        CircularDma {
+            dma,
+            prec,
+            stream,
+            buffer,
+        }
+    }
+
+    fn poll(&mut self) -> nb::Result<Half, CircularError> {
+        let flags = dma_flags(&self.dma, self.stream);
+
+        if flags & DMA_TEIF != 0 {
+            clear_dma_flags(&self.dma, self.stream);
+            Err(nb::Error::Other(CircularError::Transfer))
+        } else if flags & DMA_HTIF != 0 {
+            clear_dma_flag(&self.dma, self.stream, DMA_HTIF);
+            Ok(Half::First)
+        } else if flags & DMA_TCIF != 0 {
+            clear_dma_flag(&self.dma, self.stream, DMA_TCIF);
+            Ok(Half::Second)
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn half(&self, half: Half) -> &[u32] {
+        let mid = self.buffer.len() / 2;
+        match half {
+            Half::First => &self.buffer[..mid],
+            Half::Second => &self.buffer[mid..],
+        }
+    }
+
+    fn stop(self) -> (DMA, DMA::Rec, &'static mut [u32]) {
+        let st = &self.dma.st[self.stream];
+        st.cr.modify(|_, w| w.en().clear_bit());
+        while st.cr.read().en().bit_is_set() {}
+        clear_dma_flags(&self.dma, self.stream);
+
+        (self.dma, self.prec, self.buffer)
+    }
+}
+
+// DMA stream interrupt flags, relative to the stream's offset in
+// LISR/HISR
+const DMA_TEIF: u32 = 1 << 3;
+const DMA_HTIF: u32 = 1 << 4;
+const DMA_TCIF: u32 = 1 << 5;
+const DMA_ALL: u32 = 0b11_1101;
+
+/// Offset of the flags for `stream` in LISR/HISR
+fn dma_flag_offset(stream: usize) -> u32 {
+    [0, 6, 16, 22][stream % 4]
+}
+
+fn dma_flags(dma: &dma1::RegisterBlock, stream: usize) -> u32 {
+    let isr = if stream < 4 {
+        dma.lisr.read().bits()
+    } else {
+        dma.hisr.read().bits()
+    };
+    isr >> dma_flag_offset(stream)
+}
+
+fn clear_dma_flag(dma: &dma1::RegisterBlock, stream: usize, flag: u32) {
+    let bits = flag << dma_flag_offset(stream);
+    if stream < 4 {
+        dma.lifcr.write(|w| unsafe { w.bits(bits) });
+    } else {
+        dma.hifcr.write(|w| unsafe { w.bits(bits) });
+    }
+}
+
+fn clear_dma_flags(dma: &dma1::RegisterBlock, stream: usize) {
+    clear_dma_flag(dma, stream, DMA_ALL);
+}
+
 pub trait AdcExt<ADC>: Sized {
     type Rec: ResetEnable;
 
@@ -285,7 +1280,7 @@ pub trait AdcExt<ADC>: Sized {
 
 /// Stored ADC config can be restored using the `Adc::restore_cfg` method
 #[derive(Copy, Clone, Debug, PartialEq)]
-pub struct StoredConfig(AdcSampleTime, Resolution, AdcLshift);
+pub struct StoredConfig(AdcSampleTime, Resolution, AdcLshift, AdcOversampling);
 
 #[allow(unused_macros)]
 macro_rules! adc_hal {
@@ -293,7 +1288,8 @@ macro_rules! adc_hal {
         $ADC:ident: (
             $adcX: ident,
             $Rec:ident,
-            $COMMON:ident
+            $COMMON:ident,
+            $dmareq:expr
         )
     ),+ $(,)*) => {
         $(
@@ -317,13 +1313,7 @@ macro_rules! adc_hal {
                 pub fn $adcX(adc: $ADC, delay: &mut Delay,
                              prec: rec::$Rec, clocks: &CoreClocks
                 ) -> Self {
-                    let mut s = Self {
-                        rb: adc,
-                        sample_time: AdcSampleTime::default(),
-                        resolution: Resolution::SIXTEENBIT,
-                        lshift: AdcLshift::default(),
-                        _enabled: PhantomData,
-                    };
+                    let mut s = Self::new_unchecked(adc);
 
                     // Select Kernel Clock
                     s.enable_clock(
@@ -339,14 +1329,31 @@ macro_rules! adc_hal {
                     // Reset periperal
                     prec.reset();
 
-                    // Power Up, Preconfigure and Calibrate
-                    s.power_up(delay);
-                    s.preconfigure();
-                    s.calibrate();
-
+                    s.init(delay);
                     s
                 }
 
+                /// Create the driver with default parameters, without
+                /// accessing the peripheral
+                fn new_unchecked(adc: $ADC) -> Self {
+                    Self {
+                        rb: adc,
+                        sample_time: AdcSampleTime::default(),
+                        resolution: Resolution::SIXTEENBIT,
+                        lshift: AdcLshift::default(),
+                        oversampling: AdcOversampling::default(),
+                        _enabled: PhantomData,
+                    }
+                }
+
+                /// Power Up, Preconfigure and Calibrate, after the
+                /// peripheral has been reset
+                fn init(&mut self, delay: &mut Delay) {
+                    self.power_up(delay);
+                    self.preconfigure();
+                    self.calibrate();
+                }
+
                 fn enable_clock(&mut self, per_ck: u32) {
                     // Set per_ck as adc clock, TODO: we might want to
                     // change this so we can also use other clocks as
@@ -380,7 +1387,9 @@ macro_rules! adc_hal {
                     );
                 }
 
-                /// Calibrates the ADC in single channel mode
+                /// Calibrates the ADC. The offset is calibrated for
+                /// single-ended channels together with the linearity,
+                /// then separately for differential channels (ADCALDIF).
                 ///
                 /// Note: The ADC must be disabled
                 pub fn calibrate(&mut self) {
@@ -395,6 +1404,42 @@ macro_rules! adc_hal {
                     // calibrate
                     self.rb.cr.modify(|_, w| w.adcal().set_bit());
                     while self.rb.cr.read().adcal().bit_is_set() {}
+
+                    // differential channels, which have a separate offset
+                    // calibration. The linearity calibration is shared
+                    self.rb.cr.modify(|_, w|
+                        w.adcaldif().set_bit()
+                            .adcallin().clear_bit()
+                    );
+                    self.rb.cr.modify(|_, w| w.adcal().set_bit());
+                    while self.rb.cr.read().adcal().bit_is_set() {}
+                    self.rb.cr.modify(|_, w| w.adcaldif().clear_bit());
+                }
+
+                /// Convert the channel for `pair` differentially. The
+                /// channel is converted single-ended otherwise.
+                pub fn set_differential<P, N>(&mut self, _pair: &Differential<P, N>)
+                where
+                    Differential<P, N>: Channel<$ADC, ID = u8>,
+                {
+                    let chan = <Differential<P, N> as Channel<$ADC>>::channel();
+
+                    // Refer to RM0433 Rev 7 - Chapter 25.4.13
+                    self.rb.difsel.modify(|r, w| unsafe {
+                        w.bits(r.bits() | (1 << chan))
+                    });
+                }
+
+                /// Convert the channel for `pair` single-ended again
+                pub fn set_single_ended<P, N>(&mut self, _pair: &Differential<P, N>)
+                where
+                    Differential<P, N>: Channel<$ADC, ID = u8>,
+                {
+                    let chan = <Differential<P, N> as Channel<$ADC>>::channel();
+
+                    self.rb.difsel.modify(|r, w| unsafe {
+                        w.bits(r.bits() & !(1 << chan))
+                    });
                 }
 
                 fn check_calibration_conditions(&self) {
@@ -455,62 +1500,638 @@ macro_rules! adc_hal {
                         sample_time: self.sample_time,
                         resolution: self.resolution,
                         lshift: self.lshift,
+                        oversampling: self.oversampling,
                         _enabled: PhantomData,
                     }
                 }
             }
 
             impl Adc<$ADC, Enabled> {
-                fn stop_regular_conversion(&mut self) {
-                    self.rb.cr.modify(|_, w| w.adstp().set_bit());
-                    while self.rb.cr.read().adstp().bit_is_set() {}
-                }
+                /// Convert a differential `pair`, returning the signed
+                /// result. The pair must have been set to differential
+                /// mode with [`set_differential`](#method.set_differential)
+                /// before the ADC was enabled.
+                pub fn read_differential<P, N>(&mut self, _pair: &mut Differential<P, N>) -> i32
+                where
+                    Differential<P, N>: Channel<$ADC, ID = u8>,
+                {
+                    let chan = <Differential<P, N> as Channel<$ADC>>::channel();
+                    assert!(
+                        self.rb.difsel.read().bits() & (1 << chan) != 0,
+                        "Channel is not in differential mode"
+                    );
 
-                fn stop_injected_conversion(&mut self) {
-                    self.rb.cr.modify(|_, w| w.jadstp().set_bit());
-                    while self.rb.cr.read().jadstp().bit_is_set() {}
+                    let raw = self.convert(chan);
+                    self.differential_value(raw)
                 }
 
-                fn set_chan_smp(&mut self, chan: u8) {
-                    match chan {
-                        0 => self.rb.smpr1.modify(|_, w| w.smp0().bits(self.get_sample_time().into())),
-                        1 => self.rb.smpr1.modify(|_, w| w.smp1().bits(self.get_sample_time().into())),
-                        2 => self.rb.smpr1.modify(|_, w| w.smp2().bits(self.get_sample_time().into())),
-                        3 => self.rb.smpr1.modify(|_, w| w.smp3().bits(self.get_sample_time().into())),
-                        4 => self.rb.smpr1.modify(|_, w| w.smp4().bits(self.get_sample_time().into())),
-                        5 => self.rb.smpr1.modify(|_, w| w.smp5().bits(self.get_sample_time().into())),
-                        6 => self.rb.smpr1.modify(|_, w| w.smp6().bits(self.get_sample_time().into())),
-                        7 => self.rb.smpr1.modify(|_, w| w.smp7().bits(self.get_sample_time().into())),
-                        8 => self.rb.smpr1.modify(|_, w| w.smp8().bits(self.get_sample_time().into())),
-                        9 => self.rb.smpr1.modify(|_, w| w.smp9().bits(self.get_sample_time().into())),
-                        10 => self.rb.smpr2.modify(|_, w| w.smp10().bits(self.get_sample_time().into())),
-                        11 => self.rb.smpr2.modify(|_, w| w.smp11().bits(self.get_sample_time().into())),
-                        12 => self.rb.smpr2.modify(|_, w| w.smp12().bits(self.get_sample_time().into())),
-                        13 => self.rb.smpr2.modify(|_, w| w.smp13().bits(self.get_sample_time().into())),
-                        14 => self.rb.smpr2.modify(|_, w| w.smp14().bits(self.get_sample_time().into())),
-                        15 => self.rb.smpr2.modify(|_, w| w.smp15().bits(self.get_sample_time().into())),
-                        16 => self.rb.smpr2.modify(|_, w| w.smp16().bits(self.get_sample_time().into())),
-                        17 => self.rb.smpr2.modify(|_, w| w.smp17().bits(self.get_sample_time().into())),
-                        18 => self.rb.smpr2.modify(|_, w| w.smp18().bits(self.get_sample_time().into())),
-                        19 => self.rb.smpr2.modify(|_, w| w.smp19().bits(self.get_sample_time().into())),
-                        _ => unreachable!(),
-                    }
+                /// Returns the signed value of a `raw` differential
+                /// result, which is offset by half of the full scale.
+                /// The value is positive when INPx is above INNx.
+                pub fn differential_value(&self, raw: u32) -> i32 {
+                    let bits = self.get_resolution().number_of_bits();
+                    let mid = self.scale(1 << (bits - 1));
+
+                    raw as i32 - mid as i32
                 }
 
-                // Refer to RM0433 Rev 6 - Chapter 24.4.16
-                fn convert(&mut self, chan: u8) -> u32 {
-                    assert!(chan <= 19);
-                    self.check_conversion_conditions();
+                /// Subtract `offset` from each result of the channel for
+                /// `pin`, using the offset `register`. The results may
+                /// then be negative, see [`read_signed`](#method.read_signed).
+                ///
+                /// If `saturate` is set, the results are saturated to a
+                /// signed value of the resolution, for example an `i16`
+                /// at 16 bits. Otherwise they are sign extended to 32 bits.
+                ///
+                /// The offset registers are shared with the offsets of
+                /// an [`InjectedSequence`], and are all rewritten by
+                /// [`set_injected_sequence`](#method.set_injected_sequence).
+                /// When using both, set the channel offsets afterwards
+                /// with a register not used by a rank with an offset.
+                ///
+                /// # Panics
+                ///
+                /// Panics if `offset` does not fit in 26 bits
+                pub fn set_channel_offset<PIN>(
+                    &mut self,
+                    register: OffsetRegister,
+                    _pin: &PIN,
+                    offset: u32,
+                    saturate: bool,
+                ) where
+                    PIN: Channel<$ADC, ID = u8>,
+                {
+                    assert!(offset < (1 << 26));
+
+                    self.set_offset(register, PIN::channel(), offset, saturate);
+                }
 
-                    // Set resolution
-                    self.rb.cfgr.modify(|_, w| unsafe { w.res().bits(self.get_resolution().into()) });
+                /// Stop subtracting the offset of `register`
+                pub fn disable_channel_offset(&mut self, register: OffsetRegister) {
+                    self.set_offset(register, 0, 0, false);
+                }
 
-                    // Set LSHIFT[3:0]
-                    self.rb.cfgr2.modify(|_, w| w.lshift().bits(self.get_lshift().value()));
+                fn set_offset(&mut self, register: OffsetRegister, chan: u8, offset: u32, saturate: bool) {
+                    if self.rb.cr.read().adstart().bit_is_set() {
+                        self.stop_regular_conversion();
+                    }
+                    if self.rb.cr.read().jadstart().bit_is_set() {
+                        self.stop_injected_conversion();
+                    }
+
+                    // Refer to RM0433 Rev 7 - Chapter 25.4.26
+                    match register {
+                        OffsetRegister::Ofr1 => self.rb.ofr1.write(|w| {
+                            w.ssate().bit(saturate).offset1_ch().bits(chan).offset1().bits(offset)
+                        }),
+                        OffsetRegister::Ofr2 => self.rb.ofr2.write(|w| {
+                            w.ssate().bit(saturate).offset2_ch().bits(chan).offset2().bits(offset)
+                        }),
+                        OffsetRegister::Ofr3 => self.rb.ofr3.write(|w| {
+                            w.ssate().bit(saturate).offset3_ch().bits(chan).offset3().bits(offset)
+                        }),
+                        OffsetRegister::Ofr4 => self.rb.ofr4.write(|w| {
+                            w.ssate().bit(saturate).offset4_ch().bits(chan).offset4().bits(offset)
+                        }),
+                    }
+                }
+
+                /// Convert the channel for `pin`, returning a signed
+                /// result. Use this for channels with an offset.
+                pub fn read_signed<PIN>(&mut self, _pin: &mut PIN) -> i32
+                where
+                    PIN: Channel<$ADC, ID = u8>,
+                {
+                    self.convert(PIN::channel()) as i32
+                }
+
+                /// Start regular conversions on `edge` of `trigger`,
+                /// instead of by software
+                pub fn set_external_trigger(&mut self, trigger: ExternalTrigger, edge: TriggerEdge) {
+                    if self.rb.cr.read().adstart().bit_is_set() {
+                        self.stop_regular_conversion();
+                    }
+
+                    // Refer to RM0433 Rev 7 - Chapter 25.4.19
+                    self.rb.cfgr.modify(|_, w| {
+                        let w = unsafe { w.extsel().bits(trigger as u8) };
+                        match edge {
+                            TriggerEdge::Rising => w.exten().rising_edge(),
+                            TriggerEdge::Falling => w.exten().falling_edge(),
+                            TriggerEdge::Both => w.exten().both_edges(),
+                        }
+                    });
+                }
+
+                /// Start regular conversions by software
+                pub fn disable_external_trigger(&mut self) {
+                    if self.rb.cr.read().adstart().bit_is_set() {
+                        self.stop_regular_conversion();
+                    }
+
+                    self.rb.cfgr.modify(|_, w| w.exten().disabled());
+                }
+
+                fn stop_regular_conversion(&mut self) {
+                    self.rb.cr.modify(|_, w| w.adstp().set_bit());
+                    while self.rb.cr.read().adstp().bit_is_set() {}
+                }
+
+                fn stop_injected_conversion(&mut self) {
+                    self.rb.cr.modify(|_, w| w.jadstp().set_bit());
+                    while self.rb.cr.read().jadstp().bit_is_set() {}
+                }
+
+                fn set_chan_smp(&mut self, chan: u8, t_samp: AdcSampleTime) {
+                    let smp = t_samp.into();
+                    match chan {
+                        0 => self.rb.smpr1.modify(|_, w| w.smp0().bits(smp)),
+                        1 => self.rb.smpr1.modify(|_, w| w.smp1().bits(smp)),
+                        2 => self.rb.smpr1.modify(|_, w| w.smp2().bits(smp)),
+                        3 => self.rb.smpr1.modify(|_, w| w.smp3().bits(smp)),
+                        4 => self.rb.smpr1.modify(|_, w| w.smp4().bits(smp)),
+                        5 => self.rb.smpr1.modify(|_, w| w.smp5().bits(smp)),
+                        6 => self.rb.smpr1.modify(|_, w| w.smp6().bits(smp)),
+                        7 => self.rb.smpr1.modify(|_, w| w.smp7().bits(smp)),
+                        8 => self.rb.smpr1.modify(|_, w| w.smp8().bits(smp)),
+                        9 => self.rb.smpr1.modify(|_, w| w.smp9().bits(smp)),
+                        10 => self.rb.smpr2.modify(|_, w| w.smp10().bits(smp)),
+                        11 => self.rb.smpr2.modify(|_, w| w.smp11().bits(smp)),
+                        12 => self.rb.smpr2.modify(|_, w| w.smp12().bits(smp)),
+                        13 => self.rb.smpr2.modify(|_, w| w.smp13().bits(smp)),
+                        14 => self.rb.smpr2.modify(|_, w| w.smp14().bits(smp)),
+                        15 => self.rb.smpr2.modify(|_, w| w.smp15().bits(smp)),
+                        16 => self.rb.smpr2.modify(|_, w| w.smp16().bits(smp)),
+                        17 => self.rb.smpr2.modify(|_, w| w.smp17().bits(smp)),
+                        18 => self.rb.smpr2.modify(|_, w| w.smp18().bits(smp)),
+                        19 => self.rb.smpr2.modify(|_, w| w.smp19().bits(smp)),
+                        _ => unreachable!(),
+                    }
+                }
+
+                fn set_sequence_rank(&mut self, rank: usize, chan: u8) {
+                    // Refer to RM0433 Rev 6 - Chapter 24.6.14-17
+                    unsafe {
+                        match rank {
+                            0 => self.rb.sqr1.modify(|_, w| w.sq1().bits(chan)),
+                            1 => self.rb.sqr1.modify(|_, w| w.sq2().bits(chan)),
+                            2 => self.rb.sqr1.modify(|_, w| w.sq3().bits(chan)),
+                            3 => self.rb.sqr1.modify(|_, w| w.sq4().bits(chan)),
+                            4 => self.rb.sqr2.modify(|_, w| w.sq5().bits(chan)),
+                            5 => self.rb.sqr2.modify(|_, w| w.sq6().bits(chan)),
+                            6 => self.rb.sqr2.modify(|_, w| w.sq7().bits(chan)),
+                            7 => self.rb.sqr2.modify(|_, w| w.sq8().bits(chan)),
+                            8 => self.rb.sqr2.modify(|_, w| w.sq9().bits(chan)),
+                            9 => self.rb.sqr3.modify(|_, w| w.sq10().bits(chan)),
+                            10 => self.rb.sqr3.modify(|_, w| w.sq11().bits(chan)),
+                            11 => self.rb.sqr3.modify(|_, w| w.sq12().bits(chan)),
+                            12 => self.rb.sqr3.modify(|_, w| w.sq13().bits(chan)),
+                            13 => self.rb.sqr3.modify(|_, w| w.sq14().bits(chan)),
+                            14 => self.rb.sqr4.modify(|_, w| w.sq15().bits(chan)),
+                            15 => self.rb.sqr4.modify(|_, w| w.sq16().bits(chan)),
+                            _ => unreachable!(),
+                        }
+                    }
+                }
+
+                /// Program the resolution, LSHIFT and the ranks of
+                /// `sequence`. Returns the preselected channels
+                fn set_sequence(&mut self, sequence: &Sequence<$ADC>) -> u32 {
+                    // Set resolution
+                    self.rb.cfgr.modify(|_, w| unsafe { w.res().bits(self.get_resolution().into()) });
+
+                    // Set LSHIFT[3:0] and oversampling
+                    self.set_cfgr2();
+
+                    // Program the ranks
+                    let mut pcsel = 0;
+                    for (rank, &(chan, t_samp)) in sequence.ranks[..sequence.len].iter().enumerate() {
+                        pcsel |= 1 << chan;
+                        self.set_chan_smp(chan, t_samp);
+                        self.set_sequence_rank(rank, chan);
+                    }
+                    self.rb.sqr1.modify(|_, w| w.l().bits(sequence.len() as u8 - 1));
+                    self.rb.pcsel.modify(|r, w| unsafe { w.pcsel().bits(r.pcsel().bits() | pcsel) });
+
+                    pcsel
+                }
+
+                /// Convert `sequence` repeatedly, transferring the
+                /// results to `buffer` with a circular DMA transfer.
+                ///
+                /// Arguments:
+                /// * `sequence` - The channels to convert
+                /// * `mode` - Continuous or discontinuous conversion
+                /// * `dma` - DMA1 or DMA2
+                /// * `prec` - The reset and enable control for `dma`.
+                ///   The DMA is enabled
+                /// * `stream` - The DMA stream to use, 0 to 7
+                /// * `dmamux` - The DMA request multiplexer
+                /// * `buffer` - The buffer for the results. The DMA
+                ///   cannot access the DTCM.
+                ///
+                /// The transfer notifies when each half of `buffer` is
+                /// filled, see [`CircularTransfer::poll`]. The DMA stream
+                /// interrupt is enabled for these notifications.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the sequence is empty, or `buffer` has an
+                /// odd length or is longer than 65535 words.
+                #[allow(clippy::too_many_arguments)]
+                pub fn start_circular<DMA>(
+                    mut self,
+                    sequence: &Sequence<$ADC>,
+                    mode: ConversionMode,
+                    dma: DMA,
+                    prec: DMA::Rec,
+                    stream: usize,
+                    dmamux: &DMAMUX1,
+                    buffer: &'static mut [u32],
+                ) -> CircularTransfer<$ADC, DMA>
+                where
+                    DMA: DmaPeripheral,
+                {
+                    assert!(!sequence.is_empty());
+                    self.check_conversion_conditions();
+
+                    let dr = &self.rb.dr as *const _ as u32;
+                    let dma = CircularDma::start(dma, prec, stream, dmamux, $dmareq, dr, buffer);
+
+                    let pcsel = self.set_sequence(sequence);
+                    self.set_circular_mode(mode);
+                    self.rb.cr.modify(|_, w| w.adstart().set_bit());
+
+                    CircularTransfer {
+                        adc: self,
+                        dma,
+                        pcsel,
+                    }
+                }
+
+                /// Set the conversion mode, with DMA requests for
+                /// each result
+                fn set_circular_mode(&mut self, mode: ConversionMode) {
+                    // Refer to RM0433 Rev 7 - Chapters 25.4.15, 25.4.16, 25.4.27
+                    self.rb.cfgr.modify(|_, w| {
+                        let w = w.dmngt().dma_circular().ovrmod().preserve();
+                        match mode {
+                            ConversionMode::Continuous => w.cont().set_bit().discen().clear_bit(),
+                            ConversionMode::Discontinuous(n) => {
+                                assert!((1..=8).contains(&n));
+                                w.cont().clear_bit().discen().set_bit().discnum().bits(n - 1)
+                            }
+                        }
+                    });
+                    self.rb.isr.write(|w| w.ovr().set_bit().eos().set_bit());
+                }
+
+                /// Restore single conversions after a circular transfer,
+                /// and disable preselection of the `pcsel` channels
+                fn stop_circular_mode(&mut self, pcsel: u32) {
+                    if self.rb.cr.read().adstart().bit_is_set() {
+                        self.stop_regular_conversion();
+                    }
+
+                    self.rb.cfgr.modify(|_, w| {
+                        w.dmngt().dr().cont().clear_bit().discen().set_bit().discnum().bits(0)
+                    });
+                    self.rb.pcsel.modify(|r, w| unsafe { w.pcsel().bits(r.pcsel().bits() & !pcsel) });
+                }
+
+                /// Program the injected group. Any injected conversions
+                /// in progress are stopped.
+                ///
+                /// The offset registers are set from the offsets of
+                /// each rank. Offset registers of ranks without an
+                /// offset, or beyond the length of the sequence, are
+                /// cleared, including any set with
+                /// [`set_channel_offset`](#method.set_channel_offset).
+                ///
+                /// # Panics
+                ///
+                /// Panics if the sequence is empty
+                pub fn set_injected_sequence(&mut self, sequence: &InjectedSequence<$ADC>) {
+                    assert!(!sequence.is_empty());
+
+                    if self.rb.cr.read().jadstart().bit_is_set() {
+                        self.stop_injected_conversion();
+                    }
+
+                    // Set resolution
+                    self.rb.cfgr.modify(|_, w| unsafe { w.res().bits(self.get_resolution().into()) });
+
+                    // Set LSHIFT[3:0] and oversampling
+                    self.set_cfgr2();
+
+                    let mut pcsel = 0;
+                    let mut jsq = [0; 4];
+                    for (rank, &(chan, t_samp, _)) in sequence.ranks[..sequence.len].iter().enumerate() {
+                        pcsel |= 1 << chan;
+                        jsq[rank] = chan;
+                        self.set_chan_smp(chan, t_samp);
+                    }
+                    self.rb.pcsel.modify(|r, w| unsafe { w.pcsel().bits(r.pcsel().bits() | pcsel) });
+
+                    // Offsets, one register per rank. Registers without
+                    // an offset are cleared, so that no offset is left
+                    // from a previous sequence
+                    let registers = [
+                        OffsetRegister::Ofr1,
+                        OffsetRegister::Ofr2,
+                        OffsetRegister::Ofr3,
+                        OffsetRegister::Ofr4,
+                    ];
+                    for (rank, &register) in registers.iter().enumerate() {
+                        match sequence.ranks[..sequence.len].get(rank) {
+                            Some(&(chan, _, offset)) if offset != 0 => {
+                                self.set_offset(register, chan, offset, false)
+                            }
+                            _ => self.disable_channel_offset(register),
+                        }
+                    }
+
+                    // Refer to RM0433 Rev 7 - Chapter 25.4.21
+                    self.rb.jsqr.write(|w| unsafe {
+                        let w = w
+                            .jl()
+                            .bits(sequence.len() as u8 - 1)
+                            .jsq1()
+                            .bits(jsq[0])
+                            .jsq2()
+                            .bits(jsq[1])
+                            .jsq3()
+                            .bits(jsq[2])
+                            .jsq4()
+                            .bits(jsq[3]);
+                        match sequence.trigger {
+                            None => w.jexten().disabled(),
+                            Some((trigger, edge)) => {
+                                let w = w.jextsel().bits(trigger as u8);
+                                match edge {
+                                    TriggerEdge::Rising => w.jexten().rising_edge(),
+                                    TriggerEdge::Falling => w.jexten().falling_edge(),
+                                    TriggerEdge::Both => w.jexten().both_edges(),
+                                }
+                            }
+                        }
+                    });
+                    self.rb.isr.write(|w| w.jeos().set_bit().jeoc().set_bit());
+                }
+
+                /// Start the injected group. With a software trigger the
+                /// sequence is converted once. With a hardware trigger,
+                /// the sequence is converted on each trigger until
+                /// [`stop_injected`](#method.stop_injected) is called.
+                pub fn start_injected(&mut self) {
+                    self.check_conversion_conditions();
+
+                    self.rb.cr.modify(|_, w| w.jadstart().set_bit());
+                }
+
+                /// Stop the injected group
+                pub fn stop_injected(&mut self) {
+                    if self.rb.cr.read().jadstart().bit_is_set() {
+                        self.stop_injected_conversion();
+                    }
+                }
+
+                /// Read the results of the injected sequence into
+                /// `results`, in rank order. Returns `WouldBlock` until
+                /// the whole sequence has been converted.
+                ///
+                /// The results are signed, since an offset may make them
+                /// negative.
+                pub fn read_injected(&mut self, results: &mut [i32]) -> nb::Result<(), Void> {
+                    if self.rb.isr.read().jeos().bit_is_clear() {
+                        return Err(nb::Error::WouldBlock);
+                    }
+
+                    let jdr = [
+                        self.rb.jdr1.read().bits(),
+                        self.rb.jdr2.read().bits(),
+                        self.rb.jdr3.read().bits(),
+                        self.rb.jdr4.read().bits(),
+                    ];
+                    for (result, &data) in results.iter_mut().zip(jdr.iter()) {
+                        *result = data as i32;
+                    }
+                    self.rb.isr.write(|w| w.jeos().set_bit().jeoc().set_bit());
+
+                    Ok(())
+                }
+
+                /// Convert the injected sequence automatically after
+                /// each regular sequence (JAUTO).
+                ///
+                /// Auto-injection cannot be used together with
+                /// discontinuous regular conversions, so these are
+                /// disabled whilst it is enabled.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the injected sequence has a hardware
+                /// trigger
+                pub fn set_auto_injection(&mut self, enable: bool) {
+                    assert!(
+                        self.rb.jsqr.read().jexten().is_disabled(),
+                        "Auto-injection requires a software trigger"
+                    );
+                    if self.rb.cr.read().adstart().bit_is_set() {
+                        self.stop_regular_conversion();
+                    }
+                    self.stop_injected();
+
+                    // Refer to RM0433 Rev 7 - Chapter 25.4.21
+                    self.rb.cfgr.modify(|_, w| w.jauto().bit(enable).discen().bit(!enable));
+                }
+
+                /// Start listening for `event`
+                pub fn listen(&mut self, event: Event) {
+                    match event {
+                        Event::InjectedEndOfSequence => {
+                            self.rb.ier.modify(|_, w| w.jeosie().set_bit())
+                        }
+                        Event::AnalogWatchdog(AnalogWatchdog::Awd1) => {
+                            self.rb.ier.modify(|_, w| w.awd1ie().set_bit())
+                        }
+                        Event::AnalogWatchdog(AnalogWatchdog::Awd2) => {
+                            self.rb.ier.modify(|_, w| w.awd2ie().set_bit())
+                        }
+                        Event::AnalogWatchdog(AnalogWatchdog::Awd3) => {
+                            self.rb.ier.modify(|_, w| w.awd3ie().set_bit())
+                        }
+                    }
+                }
+
+                /// Stop listening for `event`
+                pub fn unlisten(&mut self, event: Event) {
+                    match event {
+                        Event::InjectedEndOfSequence => {
+                            self.rb.ier.modify(|_, w| w.jeosie().clear_bit())
+                        }
+                        Event::AnalogWatchdog(AnalogWatchdog::Awd1) => {
+                            self.rb.ier.modify(|_, w| w.awd1ie().clear_bit())
+                        }
+                        Event::AnalogWatchdog(AnalogWatchdog::Awd2) => {
+                            self.rb.ier.modify(|_, w| w.awd2ie().clear_bit())
+                        }
+                        Event::AnalogWatchdog(AnalogWatchdog::Awd3) => {
+                            self.rb.ier.modify(|_, w| w.awd3ie().clear_bit())
+                        }
+                    }
+                }
+
+                /// Guard `channels` with the analog watchdog `awd`,
+                /// which is triggered when a result is below `low` or
+                /// above `high`. The thresholds are compared with the
+                /// result before any offset or left shift, and are up to
+                /// 26 bits.
+                ///
+                /// # Panics
+                ///
+                /// Panics if `awd` is `Awd1` and `channels` is neither a
+                /// single channel nor all channels, or if the thresholds
+                /// do not fit in 26 bits
+                pub fn enable_watchdog(
+                    &mut self,
+                    awd: AnalogWatchdog,
+                    channels: &WatchdogChannels<$ADC>,
+                    low: u32,
+                    high: u32,
+                ) {
+                    assert!(low < (1 << 26) && high < (1 << 26));
+
+                    if self.rb.cr.read().adstart().bit_is_set() {
+                        self.stop_regular_conversion();
+                    }
+                    if self.rb.cr.read().jadstart().bit_is_set() {
+                        self.stop_injected_conversion();
+                    }
+
+                    // Refer to RM0433 Rev 7 - Chapter 25.4.28
+                    match awd {
+                        AnalogWatchdog::Awd1 => {
+                            let all = channels.mask == 0xF_FFFF;
+                            assert!(
+                                all || channels.mask.count_ones() == 1,
+                                "AWD1 guards a single channel or all channels"
+                            );
+                            let chan = channels.mask.trailing_zeros() as u8;
+
+                            self.rb.ltr1.write(|w| w.ltr1().bits(low));
+                            self.rb.htr1.write(|w| w.htr1().bits(high));
+                            self.rb.cfgr.modify(|_, w| unsafe {
+                                w.awd1sgl()
+                                    .bit(!all)
+                                    .awd1ch()
+                                    .bits(if all { 0 } else { chan })
+                                    .awd1en()
+                                    .set_bit()
+                                    .jawd1en()
+                                    .set_bit()
+                            });
+                        }
+                        AnalogWatchdog::Awd2 => {
+                            self.rb.ltr2.write(|w| w.ltr2().bits(low));
+                            self.rb.htr2.write(|w| w.htr2().bits(high));
+                            self.rb.awd2cr.write(|w| unsafe { w.bits(channels.mask) });
+                        }
+                        AnalogWatchdog::Awd3 => {
+                            self.rb.ltr3.write(|w| w.ltr3().bits(low));
+                            self.rb.htr3.write(|w| w.htr3().bits(high));
+                            self.rb.awd3cr.write(|w| unsafe { w.bits(channels.mask) });
+                        }
+                    }
+                    self.clear_watchdog(awd);
+                }
+
+                /// Stop guarding any channels with the analog watchdog
+                /// `awd`
+                pub fn disable_watchdog(&mut self, awd: AnalogWatchdog) {
+                    match awd {
+                        AnalogWatchdog::Awd1 => {
+                            if self.rb.cr.read().adstart().bit_is_set() {
+                                self.stop_regular_conversion();
+                            }
+                            if self.rb.cr.read().jadstart().bit_is_set() {
+                                self.stop_injected_conversion();
+                            }
+                            self.rb.cfgr.modify(|_, w| w.awd1en().clear_bit().jawd1en().clear_bit());
+                        }
+                        AnalogWatchdog::Awd2 => self.rb.awd2cr.reset(),
+                        AnalogWatchdog::Awd3 => self.rb.awd3cr.reset(),
+                    }
+                }
+
+                /// Returns `true` if the analog watchdog `awd` has been
+                /// triggered since it was last cleared
+                pub fn is_watchdog_triggered(&self, awd: AnalogWatchdog) -> bool {
+                    let isr = self.rb.isr.read();
+                    match awd {
+                        AnalogWatchdog::Awd1 => isr.awd1().bit_is_set(),
+                        AnalogWatchdog::Awd2 => isr.awd2().bit_is_set(),
+                        AnalogWatchdog::Awd3 => isr.awd3().bit_is_set(),
+                    }
+                }
+
+                /// Clears the flag of the analog watchdog `awd`
+                pub fn clear_watchdog(&mut self, awd: AnalogWatchdog) {
+                    match awd {
+                        AnalogWatchdog::Awd1 => self.rb.isr.write(|w| w.awd1().set_bit()),
+                        AnalogWatchdog::Awd2 => self.rb.isr.write(|w| w.awd2().set_bit()),
+                        AnalogWatchdog::Awd3 => self.rb.isr.write(|w| w.awd3().set_bit()),
+                    }
+                }
+
+                /// Convert each channel of `sequence` in turn, writing the
+                /// results to `results` in rank order.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the sequence is empty, or if `results` is
+                /// shorter than the sequence.
+                pub fn read_sequence(&mut self, sequence: &Sequence<$ADC>, results: &mut [u32]) {
+                    assert!(!sequence.is_empty());
+                    assert!(results.len() >= sequence.len());
+                    self.check_conversion_conditions();
+
+                    let pcsel = self.set_sequence(sequence);
+
+                    // Convert the whole sequence on a single start
+                    let discen = self.rb.cfgr.read().discen().bit();
+                    self.rb.cfgr.modify(|_, w| w.discen().clear_bit());
+                    self.rb.isr.write(|w| w.eos().set_bit().ovr().set_bit());
+                    self.rb.cr.modify(|_, w| w.adstart().set_bit());
+
+                    for result in results[..sequence.len()].iter_mut() {
+                        // Wait until conversion finished
+                        while self.rb.isr.read().eoc().bit_is_clear() {}
+
+                        *result = self.rb.dr.read().bits();
+                    }
+                    while self.rb.isr.read().eos().bit_is_clear() {}
+                    self.rb.isr.write(|w| w.eos().set_bit());
+
+                    // Restore single conversions, and disable preselection
+                    self.rb.cfgr.modify(|_, w| w.discen().bit(discen));
+                    self.rb.pcsel.modify(|r, w| unsafe { w.pcsel().bits(r.pcsel().bits() & !pcsel) });
+                }
+
+                // Refer to RM0433 Rev 6 - Chapter 24.4.16
+                fn convert(&mut self, chan: u8) -> u32 {
+                    assert!(chan <= 19);
+                    self.check_conversion_conditions();
+
+                    // Set resolution
+                    self.rb.cfgr.modify(|_, w| unsafe { w.res().bits(self.get_resolution().into()) });
+
+                    // Set LSHIFT[3:0] and oversampling
+                    self.set_cfgr2();
 
                     // Select channel (with preselection, refer to RM0433 Rev 6 - Chapter 24.4.12)
                     self.rb.pcsel.modify(|r, w| unsafe { w.pcsel().bits(r.pcsel().bits() | (1 << chan)) });
-                    self.set_chan_smp(chan);
+                    self.set_chan_smp(chan, self.get_sample_time());
                     self.rb.sqr1.modify(|_, w| unsafe {
                         w.sq1().bits(chan)
                             .l().bits(0)
@@ -565,11 +2186,56 @@ macro_rules! adc_hal {
                         sample_time: self.sample_time,
                         resolution: self.resolution,
                         lshift: self.lshift,
+                        oversampling: self.oversampling,
                         _enabled: PhantomData,
                     }
                 }
             }
 
+            impl<DMA> CircularTransfer<$ADC, DMA>
+            where
+                DMA: DmaPeripheral,
+            {
+                /// Returns the half of the buffer that has just been
+                /// filled, clearing the notification. This can be
+                /// called from the DMA stream interrupt.
+                ///
+                /// An overrun occurs if the DMA does not read the
+                /// results quickly enough, in which case conversions
+                /// have been lost.
+                pub fn poll(&mut self) -> nb::Result<Half, CircularError> {
+                    if self.adc.rb.isr.read().ovr().bit_is_set() {
+                        self.adc.rb.isr.write(|w| w.ovr().set_bit());
+                        Err(nb::Error::Other(CircularError::Overrun))
+                    } else {
+                        self.dma.poll()
+                    }
+                }
+
+                /// Returns one half of the buffer. The DMA is writing to
+                /// the other half, and will return to this half after
+                /// that is filled.
+                pub fn half(&self, half: Half) -> &[u32] {
+                    self.dma.half(half)
+                }
+
+                /// Start the next conversions in discontinuous mode with
+                /// a software trigger
+                pub fn trigger(&mut self) {
+                    self.adc.rb.cr.modify(|_, w| w.adstart().set_bit());
+                }
+
+                /// Stop the conversions and the DMA transfer
+                pub fn stop(self) -> (Adc<$ADC, Enabled>, DMA, DMA::Rec, &'static mut [u32]) {
+                    let CircularTransfer { mut adc, dma, pcsel } = self;
+
+                    adc.stop_circular_mode(pcsel);
+                    let (dma, prec, buffer) = dma.stop();
+
+                    (adc, dma, prec, buffer)
+                }
+            }
+
             impl<ED> Adc<$ADC, ED> {
 
                 /// Releases the ADC peripheral
@@ -579,7 +2245,12 @@ macro_rules! adc_hal {
 
                 /// Save current ADC config
                 pub fn save_cfg(&mut self) -> StoredConfig {
-                    StoredConfig(self.get_sample_time(), self.get_resolution(), self.get_lshift())
+                    StoredConfig(
+                        self.get_sample_time(),
+                        self.get_resolution(),
+                        self.get_lshift(),
+                        self.get_oversampling(),
+                    )
                 }
 
                 /// Restore saved ADC config
@@ -587,6 +2258,7 @@ macro_rules! adc_hal {
                     self.set_sample_time(cfg.0);
                     self.set_resolution(cfg.1);
                     self.set_lshift(cfg.2);
+                    self.set_oversampling(cfg.3);
                 }
 
                 /// Reset the ADC config to default, return existing config
@@ -595,6 +2267,7 @@ macro_rules! adc_hal {
                     self.set_sample_time(AdcSampleTime::default());
                     self.set_resolution(Resolution::SIXTEENBIT);
                     self.set_lshift(AdcLshift::default());
+                    self.set_oversampling(AdcOversampling::default());
                     cfg
                 }
 
@@ -613,6 +2286,11 @@ macro_rules! adc_hal {
                     self.lshift
                 }
 
+                /// Get ADC oversampling
+                pub fn get_oversampling(&self) -> AdcOversampling {
+                    self.oversampling
+                }
+
                 /// Set ADC sampling time
                 ///
                 /// Options can be found in [AdcSampleTime](crate::adc::AdcSampleTime).
@@ -632,9 +2310,60 @@ macro_rules! adc_hal {
                     self.lshift = lshift;
                 }
 
+                /// Set ADC oversampling
+                ///
+                /// Options can be found in [AdcOversampling](crate::adc::AdcOversampling).
+                pub fn set_oversampling(&mut self, oversampling: AdcOversampling) {
+                    self.oversampling = oversampling;
+                }
+
                 /// Returns the largest possible sample value for the current settings
                 pub fn max_sample(&self) -> u32 {
-                    ((1 << self.get_resolution().number_of_bits() as u32) - 1) << self.get_lshift().value() as u32
+                    self.scale((1 << self.get_resolution().number_of_bits() as u32) - 1)
+                }
+
+                /// Scale a conversion `value` by the regular
+                /// oversampling and LSHIFT
+                fn scale(&self, value: u32) -> u32 {
+                    let (lshift, ratio, rshift) = self.scaling();
+
+                    scale(value, lshift, ratio, rshift)
+                }
+
+                /// Returns the LSHIFT, and the ratio and right shift of
+                /// the regular oversampling, which scale each result
+                fn scaling(&self) -> (u8, u16, u8) {
+                    let os = self.get_oversampling();
+                    let (ratio, rshift) = if os.regular {
+                        (os.ratio, os.rshift)
+                    } else {
+                        (1, 0)
+                    };
+
+                    (self.get_lshift().value(), ratio, rshift)
+                }
+
+                /// Write the LSHIFT and oversampling settings to CFGR2
+                fn set_cfgr2(&mut self) {
+                    let os = self.get_oversampling();
+
+                    // Refer to RM0433 Rev 7 - Chapter 25.4.31
+                    self.rb.cfgr2.modify(|_, w| unsafe {
+                        w.lshift()
+                            .bits(self.get_lshift().value())
+                            .osvr()
+                            .bits(os.ratio - 1)
+                            .ovss()
+                            .bits(os.rshift)
+                            .rovse()
+                            .bit(os.regular)
+                            .jovse()
+                            .bit(os.injected)
+                            .trovs()
+                            .bit(os.triggered)
+                            .rovsm()
+                            .bit(os.mode == OversamplingMode::Resumed)
+                    });
                 }
 
                                 /// Returns the offset calibration value for single ended channel
@@ -642,6 +2371,76 @@ macro_rules! adc_hal {
                     AdcCalOffset(self.rb.calfact.read().calfact_s().bits())
                 }
 
+                /// Returns the offset calibration value for differential channels
+                pub fn read_differential_offset_calibration_value(&self) -> AdcCalOffset {
+                    AdcCalOffset(self.rb.calfact.read().calfact_d().bits())
+                }
+
+                /// Restore the offset calibration value for single ended
+                /// channels, rather than calibrating again
+                pub fn write_offset_calibration_value(&mut self, cal: AdcCalOffset) {
+                    self.check_calibration_write_conditions();
+
+                    self.rb.calfact.modify(|_, w| w.calfact_s().bits(cal.value()));
+                }
+
+                /// Restore the offset calibration value for
+                /// differential channels, rather than calibrating again
+                pub fn write_differential_offset_calibration_value(&mut self, cal: AdcCalOffset) {
+                    self.check_calibration_write_conditions();
+
+                    self.rb.calfact.modify(|_, w| w.calfact_d().bits(cal.value()));
+                }
+
+                /// Restore the linear calibration values, as returned by
+                /// [`read_linear_calibration_values`](#method.read_linear_calibration_values),
+                /// rather than calibrating again
+                pub fn write_linear_calibration_values(&mut self, cal: AdcCalLinear) {
+                    // Refer to RM0433 Rev 7 - Chapter 25.4.8
+                    self.check_linear_read_conditions();
+                    self.check_calibration_write_conditions();
+
+                    let value = cal.value();
+
+                    // Write each block of linear correction
+                    self.rb.calfact2.write(|w| w.lincalfact().bits(value[0]));
+                    self.rb.cr.modify(|_, w| w.lincalrdyw1().set_bit());
+                    while self.rb.cr.read().lincalrdyw1().bit_is_clear() {}
+
+                    self.rb.calfact2.write(|w| w.lincalfact().bits(value[1]));
+                    self.rb.cr.modify(|_, w| w.lincalrdyw2().set_bit());
+                    while self.rb.cr.read().lincalrdyw2().bit_is_clear() {}
+
+                    self.rb.calfact2.write(|w| w.lincalfact().bits(value[2]));
+                    self.rb.cr.modify(|_, w| w.lincalrdyw3().set_bit());
+                    while self.rb.cr.read().lincalrdyw3().bit_is_clear() {}
+
+                    self.rb.calfact2.write(|w| w.lincalfact().bits(value[3]));
+                    self.rb.cr.modify(|_, w| w.lincalrdyw4().set_bit());
+                    while self.rb.cr.read().lincalrdyw4().bit_is_clear() {}
+
+                    self.rb.calfact2.write(|w| w.lincalfact().bits(value[4]));
+                    self.rb.cr.modify(|_, w| w.lincalrdyw5().set_bit());
+                    while self.rb.cr.read().lincalrdyw5().bit_is_clear() {}
+
+                    self.rb.calfact2.write(|w| w.lincalfact().bits(value[5]));
+                    self.rb.cr.modify(|_, w| w.lincalrdyw6().set_bit());
+                    while self.rb.cr.read().lincalrdyw6().bit_is_clear() {}
+                }
+
+                fn check_calibration_write_conditions(&self) {
+                    // The ADC must be enabled and idle
+                    if self.rb.cr.read().aden().bit_is_clear() {
+                        panic!("Cannot write calibration values when the ADC is disabled");
+                    }
+                    if self.rb.cr.read().adcal().bit_is_set() {
+                        panic!("Cannot write calibration values during a calibration");
+                    }
+                    if self.rb.cr.read().adstart().bit_is_set() || self.rb.cr.read().jadstart().bit_is_set() {
+                        panic!("Cannot write calibration values during a conversion");
+                    }
+                }
+
                 /// Returns the linear calibration values stored in an array in the following order:
                 /// LINCALRDYW1 -> result\[0\]
                 /// ...
@@ -714,7 +2513,545 @@ macro_rules! adc_hal {
 }
 
 adc_hal!(
-    ADC1: (adc1, Adc12, ADC12_COMMON),
-    ADC2: (adc2, Adc12, ADC12_COMMON),
-    ADC3: (adc3, Adc3, ADC3_COMMON),
+    // (constructor, rec, common, DMAMUX1 request)
+    ADC1: (adc1, Adc12, ADC12_COMMON, 9),
+    ADC2: (adc2, Adc12, ADC12_COMMON, 10),
+    ADC3: (adc3, Adc3, ADC3_COMMON, 115),
 );
+
+/// Dual mode of ADC1 and ADC2
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DualMode {
+    /// ADC1 and ADC2 convert their regular sequences at the same instant
+    RegularSimultaneous,
+    /// ADC1 and ADC2 convert their injected sequences at the same
+    /// instant
+    InjectedSimultaneous,
+    /// ADC1 and ADC2 convert the same channel alternately, doubling the
+    /// sample rate. The argument is the delay between the start of the
+    /// ADC1 and ADC2 conversions, as the DELAY field of ADC12_CCR (0 to
+    /// 15)
+    Interleaved(u8),
+}
+
+/// ADC1 and ADC2 operating in a dual mode, with ADC1 as the master
+pub struct DualAdc {
+    master: Adc<ADC1, Enabled>,
+    slave: Adc<ADC2, Enabled>,
+    mode: DualMode,
+}
+
+/// Conversions of ADC1 and ADC2 in a dual mode, transferred to a buffer
+/// by a circular DMA transfer
+///
+/// Each word of the buffer holds a result from ADC1 in bits 15:0 and the
+/// corresponding result from ADC2 in bits 31:16, see
+/// [`DualAdc::unpack`].
+pub struct DualCircularTransfer<DMA: DmaPeripheral> {
+    adc: DualAdc,
+    dma: CircularDma<DMA>,
+    pcsel: (u32, u32),
+}
+
+impl Adc<ADC1, Disabled> {
+    /// Initialise ADC1 and ADC2, which share a kernel clock and reset
+    ///
+    /// Sets all configurable parameters to one-shot defaults, performs a
+    /// boot-time calibration.
+    pub fn adc12(
+        adc1: ADC1,
+        adc2: ADC2,
+        delay: &mut Delay,
+        prec: rec::Adc12,
+        clocks: &CoreClocks,
+    ) -> (Adc<ADC1, Disabled>, Adc<ADC2, Disabled>) {
+        let mut master = Adc::<ADC1, Disabled>::new_unchecked(adc1);
+        let mut slave = Adc::<ADC2, Disabled>::new_unchecked(adc2);
+
+        // Select Kernel Clock
+        master.enable_clock(clocks.per_ck().expect("per_ck is not running!").0);
+
+        // Enable AHB clock
+        let prec = prec.enable();
+
+        // Power Down
+        master.power_down();
+        slave.power_down();
+
+        // Reset both peripherals
+        prec.reset();
+
+        master.init(delay);
+        slave.init(delay);
+
+        (master, slave)
+    }
+}
+
+impl DualAdc {
+    /// Enable ADC1 and ADC2 in a dual `mode`
+    pub fn new(
+        adc1: Adc<ADC1, Disabled>,
+        adc2: Adc<ADC2, Disabled>,
+        mode: DualMode,
+    ) -> Self {
+        let common = unsafe { &*ADC12_COMMON::ptr() };
+
+        // Refer to RM0433 Rev 7 - Chapter 25.4.32
+        common.ccr.modify(|_, w| match mode {
+            DualMode::RegularSimultaneous => w.dual().dual_r(),
+            DualMode::InjectedSimultaneous => w.dual().dual_j(),
+            DualMode::Interleaved(delay) => {
+                assert!(delay <= 15);
+                w.dual().dual_i().delay().bits(delay)
+            }
+        });
+
+        DualAdc {
+            master: adc1.enable(),
+            slave: adc2.enable(),
+            mode,
+        }
+    }
+
+    /// Returns the ADC1 and ADC2 results packed in `data`, a word from
+    /// the buffer of a [`DualCircularTransfer`]
+    pub fn unpack(data: u32) -> (u16, u16) {
+        (data as u16, (data >> 16) as u16)
+    }
+
+    /// Convert `sequence1` on ADC1 and `sequence2` on ADC2
+    /// simultaneously, writing pairs of results to `results` in rank
+    /// order.
+    ///
+    /// The sequences should have the same sample time at each rank, and
+    /// must not convert the same channel at the same time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mode is not `RegularSimultaneous`, if the sequences
+    /// are empty or of different lengths, or if `results` is shorter
+    /// than the sequences
+    pub fn read_simultaneous(
+        &mut self,
+        sequence1: &Sequence<ADC1>,
+        sequence2: &Sequence<ADC2>,
+        results: &mut [(u32, u32)],
+    ) {
+        assert!(self.mode == DualMode::RegularSimultaneous);
+        assert!(!sequence1.is_empty() && sequence1.len() == sequence2.len());
+        assert!(results.len() >= sequence1.len());
+        self.master.check_conversion_conditions();
+        self.slave.check_conversion_conditions();
+
+        let pcsel1 = self.master.set_sequence(sequence1);
+        let pcsel2 = self.slave.set_sequence(sequence2);
+
+        // Convert the whole sequences on a single start of the master
+        let discen = self.master.rb.cfgr.read().discen().bit();
+        self.master.rb.cfgr.modify(|_, w| w.discen().clear_bit());
+        self.master
+            .rb
+            .isr
+            .write(|w| w.eos().set_bit().ovr().set_bit());
+        self.slave
+            .rb
+            .isr
+            .write(|w| w.eos().set_bit().ovr().set_bit());
+        self.master.rb.cr.modify(|_, w| w.adstart().set_bit());
+
+        for result in results[..sequence1.len()].iter_mut() {
+            while self.master.rb.isr.read().eoc().bit_is_clear() {}
+            while self.slave.rb.isr.read().eoc().bit_is_clear() {}
+            *result = (
+                self.master.rb.dr.read().bits(),
+                self.slave.rb.dr.read().bits(),
+            );
+        }
+        while self.master.rb.isr.read().eos().bit_is_clear() {}
+        self.master.rb.isr.write(|w| w.eos().set_bit());
+        self.slave.rb.isr.write(|w| w.eos().set_bit());
+
+        // Restore single conversions, and disable preselection
+        self.master.rb.cfgr.modify(|_, w| w.discen().bit(discen));
+        self.master.rb.pcsel.modify(|r, w| unsafe {
+            w.pcsel().bits(r.pcsel().bits() & !pcsel1)
+        });
+        self.slave.rb.pcsel.modify(|r, w| unsafe {
+            w.pcsel().bits(r.pcsel().bits() & !pcsel2)
+        });
+    }
+
+    /// Convert `sequence1` on ADC1 and `sequence2` on ADC2 repeatedly,
+    /// transferring pairs of results packed in the common data register
+    /// (CDR) to `buffer` with a circular DMA transfer.
+    ///
+    /// In `RegularSimultaneous` mode, the sequences are converted at the
+    /// same instant. In `Interleaved` mode, both sequences must contain
+    /// the same single channel.
+    ///
+    /// The arguments are as for [`Adc::start_circular`], with the DMA
+    /// request from ADC1. Results are truncated to 16 bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mode is `InjectedSimultaneous`, if the sequences
+    /// are empty or of different lengths, or in `Interleaved` mode if
+    /// the sequences are not the same single channel.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_circular<DMA>(
+        mut self,
+        sequence1: &Sequence<ADC1>,
+        sequence2: &Sequence<ADC2>,
+        mode: ConversionMode,
+        dma: DMA,
+        prec: DMA::Rec,
+        stream: usize,
+        dmamux: &DMAMUX1,
+        buffer: &'static mut [u32],
+    ) -> DualCircularTransfer<DMA>
+    where
+        DMA: DmaPeripheral,
+    {
+        assert!(self.mode != DualMode::InjectedSimultaneous);
+        assert!(!sequence1.is_empty() && sequence1.len() == sequence2.len());
+        if let DualMode::Interleaved(_) = self.mode {
+            assert!(
+                sequence1.len() == 1
+                    && sequence1.ranks[0].0 == sequence2.ranks[0].0
+            );
+        }
+        self.master.check_conversion_conditions();
+        self.slave.check_conversion_conditions();
+
+        // A single DMA request for each pair of results, 32-bit format
+        let common = unsafe { &*ADC12_COMMON::ptr() };
+        common.ccr.modify(|_, w| w.damdf().format32to10());
+
+        let cdr = &common.cdr as *const _ as u32;
+        let dma = CircularDma::start(dma, prec, stream, dmamux, 9, cdr, buffer);
+
+        let pcsel = (
+            self.master.set_sequence(sequence1),
+            self.slave.set_sequence(sequence2),
+        );
+        self.master.set_circular_mode(mode);
+        self.slave
+            .rb
+            .isr
+            .write(|w| w.ovr().set_bit().eos().set_bit());
+        self.master.rb.cr.modify(|_, w| w.adstart().set_bit());
+
+        DualCircularTransfer {
+            adc: self,
+            dma,
+            pcsel,
+        }
+    }
+
+    /// Program the injected groups of ADC1 and ADC2. Both are started
+    /// by the trigger of `sequence1`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mode is not `InjectedSimultaneous`, or if the
+    /// sequences are empty or of different lengths
+    pub fn set_injected_sequences(
+        &mut self,
+        sequence1: &InjectedSequence<ADC1>,
+        sequence2: &InjectedSequence<ADC2>,
+    ) {
+        assert!(self.mode == DualMode::InjectedSimultaneous);
+        assert!(sequence1.len() == sequence2.len());
+
+        self.master.set_injected_sequence(sequence1);
+        self.slave.set_injected_sequence(sequence2);
+    }
+
+    /// Start the injected groups, see [`Adc::start_injected`]
+    pub fn start_injected(&mut self) {
+        self.master.start_injected();
+    }
+
+    /// Stop the injected groups
+    pub fn stop_injected(&mut self) {
+        self.master.stop_injected();
+        self.slave.stop_injected();
+    }
+
+    /// Read the results of the injected sequences of ADC1 and ADC2.
+    /// Returns `WouldBlock` until both sequences have been converted.
+    pub fn read_injected(
+        &mut self,
+        results1: &mut [i32],
+        results2: &mut [i32],
+    ) -> nb::Result<(), Void> {
+        if self.slave.rb.isr.read().jeos().bit_is_clear() {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.master.read_injected(results1)?;
+        self.slave.read_injected(results2)
+    }
+
+    /// Start listening for `event` on ADC1. The ADC1 and ADC2 events
+    /// share an interrupt
+    pub fn listen(&mut self, event: Event) {
+        self.master.listen(event);
+    }
+
+    /// Stop listening for `event` on ADC1
+    pub fn unlisten(&mut self, event: Event) {
+        self.master.unlisten(event);
+    }
+
+    /// Disable ADC1 and ADC2, returning them to independent mode
+    pub fn free(self) -> (Adc<ADC1, Disabled>, Adc<ADC2, Disabled>) {
+        let master = self.master.disable();
+        let slave = self.slave.disable();
+
+        let common = unsafe { &*ADC12_COMMON::ptr() };
+        common
+            .ccr
+            .modify(|_, w| w.dual().independent().delay().bits(0));
+
+        (master, slave)
+    }
+}
+
+impl<DMA> DualCircularTransfer<DMA>
+where
+    DMA: DmaPeripheral,
+{
+    /// Returns the half of the buffer that has just been filled,
+    /// clearing the notification. See [`CircularTransfer::poll`]
+    pub fn poll(&mut self) -> nb::Result<Half, CircularError> {
+        let master = &self.adc.master.rb;
+        let slave = &self.adc.slave.rb;
+
+        if master.isr.read().ovr().bit_is_set()
+            || slave.isr.read().ovr().bit_is_set()
+        {
+            master.isr.write(|w| w.ovr().set_bit());
+            slave.isr.write(|w| w.ovr().set_bit());
+            Err(nb::Error::Other(CircularError::Overrun))
+        } else {
+            self.dma.poll()
+        }
+    }
+
+    /// Returns one half of the buffer. The DMA is writing to the other
+    /// half, and will return to this half after that is filled.
+    pub fn half(&self, half: Half) -> &[u32] {
+        self.dma.half(half)
+    }
+
+    /// Start the next conversions in discontinuous mode with a software
+    /// trigger
+    pub fn trigger(&mut self) {
+        self.adc.master.rb.cr.modify(|_, w| w.adstart().set_bit());
+    }
+
+    /// Stop the conversions and the DMA transfer
+    pub fn stop(self) -> (DualAdc, DMA, DMA::Rec, &'static mut [u32]) {
+        let DualCircularTransfer {
+            mut adc,
+            dma,
+            pcsel,
+        } = self;
+
+        adc.master.stop_circular_mode(pcsel.0);
+        adc.slave.stop_circular_mode(pcsel.1);
+        let (dma, prec, buffer) = dma.stop();
+
+        let common = unsafe { &*ADC12_COMMON::ptr() };
+        common.ccr.modify(|_, w| w.damdf().no_pack());
+
+        (adc, dma, prec, buffer)
+    }
+}
+
+impl Adc<ADC3, Enabled> {
+    /// Returns the analog supply voltage VDDA in millivolts, calculated
+    /// from a conversion of the internal reference and its factory
+    /// calibration
+    ///
+    /// The internal reference must be enabled, and the sample time
+    /// must meet its minimum sampling time. Returns `None` if the
+    /// internal reference converts to zero, for example because it is
+    /// not enabled.
+    pub fn read_vdda(&mut self, _vrefint: &mut Vrefint) -> Option<u32> {
+        let chan = <Vrefint as Channel<ADC3>>::channel();
+        let result = self.convert(chan);
+        let sample = self.normalize(result);
+
+        vdda_from_vrefint(sample, VREFIN_CAL::get().read())
+    }
+
+    /// Returns the die temperature in °C, calculated from a conversion
+    /// of the temperature sensor and its factory calibration
+    ///
+    /// `vdda` is the analog supply voltage in millivolts, see
+    /// [`read_vdda`](#method.read_vdda). The temperature sensor must be
+    /// enabled, and the sample time must meet its minimum sampling time.
+    pub fn read_temperature(
+        &mut self,
+        _sensor: &mut Temperature,
+        vdda: u32,
+    ) -> f32 {
+        let chan = <Temperature as Channel<ADC3>>::channel();
+        let result = self.convert(chan);
+        let sample = self.normalize(result);
+
+        temperature(
+            sample,
+            vdda,
+            TS_CAL_30::get().read(),
+            TS_CAL_110::get().read(),
+        )
+    }
+
+    /// Returns the backup battery voltage VBAT in millivolts
+    ///
+    /// `vdda` is the analog supply voltage in millivolts, see
+    /// [`read_vdda`](#method.read_vdda). The VBAT channel must be
+    /// enabled.
+    pub fn read_vbat(&mut self, _vbat: &mut Vbat, vdda: u32) -> u32 {
+        let chan = <Vbat as Channel<ADC3>>::channel();
+        let result = self.convert(chan);
+        let sample = self.normalize(result);
+
+        // VBAT is measured through a 1/4 divider
+        4 * millivolts(sample, vdda)
+    }
+
+    /// Scale a conversion `result` to 16 bits, as for the factory
+    /// calibration
+    fn normalize(&self, result: u32) -> u32 {
+        let (lshift, ratio, rshift) = self.scaling();
+
+        normalize(
+            result,
+            self.get_resolution().number_of_bits(),
+            lshift,
+            ratio,
+            rshift,
+        )
+    }
+}
+
+/// Scale a conversion `value` by the oversampling `ratio` and `rshift`,
+/// and then by `lshift`. The result saturates at `u32::MAX`
+fn scale(value: u32, lshift: u8, ratio: u16, rshift: u8) -> u32 {
+    let value = (u64::from(value) * u64::from(ratio)) >> rshift << lshift;
+
+    cmp::min(value, u64::from(u32::MAX)) as u32
+}
+
+/// Scale a conversion `result` to a 16 bit sample without oversampling
+/// or left shift
+fn normalize(
+    result: u32,
+    bits: u32,
+    lshift: u8,
+    ratio: u16,
+    rshift: u8,
+) -> u32 {
+    let result = u64::from(result >> lshift) << rshift;
+    let result = result / u64::from(ratio);
+
+    (result as u32) << (16 - bits)
+}
+
+/// VDDA in millivolts from a 16 bit `sample` of the internal reference.
+/// Returns `None` for a zero sample
+fn vdda_from_vrefint(sample: u32, vrefin_cal: u16) -> Option<u32> {
+    (VDDA_CALIB * u32::from(vrefin_cal)).checked_div(sample)
+}
+
+/// Millivolts from a 16 bit `sample`, when VDDA is `vdda` millivolts
+fn millivolts(sample: u32, vdda: u32) -> u32 {
+    sample * vdda / 65535
+}
+
+/// Temperature in °C from a 16 bit `sample` of the temperature sensor,
+/// when VDDA is `vdda` millivolts
+fn temperature(sample: u32, vdda: u32, ts_cal_30: u16, ts_cal_110: u16) -> f32 {
+    // The calibration values were measured with VDDA at VDDA_CALIB
+    let sample = sample as f32 * vdda as f32 / VDDA_CALIB as f32;
+    let slope = (110.0 - 30.0) / (ts_cal_110 as f32 - ts_cal_30 as f32);
+
+    slope * (sample - ts_cal_30 as f32) + 30.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_resolution_and_lshift() {
+        // 16 bit
+        assert_eq!(normalize(0x1234, 16, 0, 1, 0), 0x1234);
+        // 12 bit, with and without left shift
+        assert_eq!(normalize(0x123, 12, 0, 1, 0), 0x1230);
+        assert_eq!(normalize(0x123 << 4, 12, 4, 1, 0), 0x1230);
+        // 8 bit
+        assert_eq!(normalize(0xAB, 8, 0, 1, 0), 0xAB00);
+        assert_eq!(normalize(0xAB << 15, 8, 15, 1, 0), 0xAB00);
+    }
+
+    #[test]
+    fn normalize_oversampling() {
+        // 16 conversions shifted by 4 is already an average
+        assert_eq!(normalize(0x1234, 16, 0, 16, 4), 0x1234);
+        // 16 conversions without shift
+        assert_eq!(normalize(0x1234 * 16, 16, 0, 16, 0), 0x1234);
+        // 1024 conversions of 16 bits, shifted by 10
+        assert_eq!(normalize(0xFFFF, 16, 0, 1024, 10), 0xFFFF);
+        // 12 bit, 4 conversions shifted by 1
+        assert_eq!(normalize(0x123 * 2, 12, 0, 4, 1), 0x1230);
+    }
+
+    #[test]
+    fn scale_oversampling_and_lshift() {
+        assert_eq!(scale(0xFFF, 0, 1, 0), 0xFFF);
+        assert_eq!(scale(0xFFF, 4, 1, 0), 0xFFF0);
+        assert_eq!(scale(0xFFFF, 0, 1024, 10), 0xFFFF);
+        assert_eq!(scale(0xFFFF, 0, 1024, 0), 0x3FF_FC00);
+        // Saturates instead of overflowing
+        assert_eq!(scale(0xFFFF, 15, 1024, 0), u32::MAX);
+        // The inverse of normalize
+        assert_eq!(normalize(scale(0x123, 4, 4, 1), 12, 4, 4, 1), 0x1230);
+    }
+
+    #[test]
+    fn vdda() {
+        // At the calibration voltage
+        assert_eq!(vdda_from_vrefint(24_000, 24_000), Some(3300));
+        // A lower VDDA gives a larger reading
+        assert_eq!(vdda_from_vrefint(31_680, 24_000), Some(2500));
+        // No reading of the internal reference
+        assert_eq!(vdda_from_vrefint(0, 24_000), None);
+    }
+
+    #[test]
+    fn voltage() {
+        assert_eq!(millivolts(0, 3300), 0);
+        assert_eq!(millivolts(65535, 3300), 3300);
+        assert_eq!(millivolts(32768, 3300), 1650);
+        // VBAT of 3V through the 1/4 divider, with VDDA = 3.3V
+        assert_eq!(4 * millivolts(14_895, 3300), 3000);
+    }
+
+    #[test]
+    fn temperature_calibration_points() {
+        let (cal30, cal110) = (12_000, 16_000);
+
+        let at = |sample, vdda| temperature(sample, vdda, cal30, cal110);
+        assert!((at(12_000, 3300) - 30.0).abs() < 0.01);
+        assert!((at(16_000, 3300) - 110.0).abs() < 0.01);
+        assert!((at(14_000, 3300) - 70.0).abs() < 0.01);
+
+        // The same temperature gives a larger reading at a lower VDDA
+        assert!((at(15_840, 2500) - 30.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,475 @@
+//! Shared SPI and I2C busses
+//!
+//! A single SPI or I2C peripheral is often connected to several
+//! devices. This module takes ownership of the peripheral driver, and
+//! hands out a proxy for each device. Each proxy implements the
+//! blocking [embedded-hal](embedded_hal) traits, so it can be passed to
+//! a device driver as if it were the bus itself.
+//!
+//! Each transaction locks the bus, so proxies may be used from
+//! different interrupt handlers or RTIC tasks. By default, interrupts
+//! are not masked whilst the bus is locked. Instead, a transaction
+//! started whilst another is in progress, for example from an interrupt
+//! handler that preempted it, returns [`Error::Busy`](Error::Busy)
+//! without touching the bus. Device drivers usually treat any error as
+//! a failure, so either arrange that the proxies for a bus are only
+//! used at one priority level, or create the bus with
+//! `new_critical_section`. Each transaction then runs in a critical
+//! section and never returns `Busy`, at the cost of masking interrupts
+//! for its duration.
+//!
+//! ```
+//! let shared = bus::SharedI2c::new_critical_section(i2c);
+//! ```
+//!
+//! # SPI
+//!
+//! Each SPI device has its own chip select pin, and its own SPI
+//! configuration and bitrate. The SPI is reconfigured before a
+//! transaction if the previous transaction was for a device with a
+//! different configuration.
+//!
+//! ```
+//! use stm32h7xx_hal::{bus, spi};
+//!
+//! let spi: spi::Spi<_, u8> = dp.SPI1.spi((sck, miso, mosi), spi::MODE_0, 1.mhz(), ccdr.peripheral.SPI1, &ccdr.clocks)?;
+//! let shared = bus::SharedSpi::new(spi, &ccdr.clocks);
+//!
+//! let mut flash = shared.acquire(cs0, spi::MODE_0, 20.mhz());
+//! let mut adc = shared.acquire(cs1, spi::MODE_3, 1.mhz());
+//!
+//! flash.write(&[0x06])?;
+//! ```
+//!
+//! The chip select pins are driven by the proxies, so the SPI itself
+//! should not be configured with [`Config::manage_cs`](crate::spi::Config::manage_cs).
+//!
+//! # I2C
+//!
+//! I2C devices are distinguished by their address, so the proxies
+//! simply forward each transaction to the bus. As well as the
+//! embedded-hal traits, the proxies provide the 10-bit addressing and
+//! [`transaction`](I2cProxy::transaction) methods of the I2C driver.
+//!
+//! ```
+//! let i2c = dp.I2C1.i2c((scl, sda), 100.khz(), ccdr.peripheral.I2C1, &ccdr.clocks, &mut dp.SYSCFG)?;
+//! let shared = bus::SharedI2c::new(i2c);
+//!
+//! let mut sensor = shared.acquire();
+//! let mut eeprom = shared.acquire();
+//! ```
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use cortex_m::interrupt;
+
+use crate::hal::blocking::i2c::{Read, Write as I2cWrite, WriteRead};
+use crate::hal::blocking::spi::{Transfer, Write};
+use crate::hal::digital::v2::OutputPin;
+use crate::i2c::{self, I2c};
+use crate::rcc::CoreClocks;
+use crate::spi::{self, Config, Spi};
+use crate::stm32::{I2C1, I2C2, I2C3, I2C4};
+use crate::stm32::{SPI1, SPI2, SPI3, SPI4, SPI5, SPI6};
+use crate::time::Hertz;
+use crate::Never;
+
+/// Shared bus error
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Error<E> {
+    /// Another transaction is in progress on the bus
+    Busy,
+    /// Error from the bus driver
+    Bus(E),
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Self {
+        Error::Bus(e)
+    }
+}
+
+/// A bus that can be locked for the duration of a transaction
+struct BusLock<T> {
+    locked: AtomicBool,
+    /// Lock the bus in a critical section
+    critical_section: bool,
+    bus: UnsafeCell<T>,
+}
+
+// The bus is only accessed whilst `locked` is held
+unsafe impl<T: Send> Sync for BusLock<T> {}
+
+impl<T> BusLock<T> {
+    fn new(bus: T, critical_section: bool) -> Self {
+        BusLock {
+            locked: AtomicBool::new(false),
+            critical_section,
+            bus: UnsafeCell::new(bus),
+        }
+    }
+
+    /// Run `f` with the bus locked. Without a critical section, returns
+    /// [`Error::Busy`](Error::Busy) if it is already locked
+    fn lock<R, E>(
+        &self,
+        f: impl FnOnce(&mut T) -> Result<R, E>,
+    ) -> Result<R, Error<E>> {
+        if self.critical_section {
+            // Nothing can preempt the transaction, so the lock is
+            // always free
+            interrupt::free(|_| self.try_lock(f))
+        } else {
+            self.try_lock(f)
+        }
+    }
+
+    /// Run `f` with the bus locked, or return
+    /// [`Error::Busy`](Error::Busy) if it is already locked
+    fn try_lock<R, E>(
+        &self,
+        f: impl FnOnce(&mut T) -> Result<R, E>,
+    ) -> Result<R, Error<E>> {
+        if self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(Error::Busy);
+        }
+
+        let result = f(unsafe { &mut *self.bus.get() });
+        self.locked.store(false, Ordering::Release);
+
+        result.map_err(Error::Bus)
+    }
+
+    fn into_inner(self) -> T {
+        self.bus.into_inner()
+    }
+}
+
+struct SpiBus<SPI, WORD> {
+    spi: Spi<SPI, WORD>,
+    clocks: CoreClocks,
+    /// The configuration and bitrate of the last transaction
+    current: Option<(Config, u32)>,
+}
+
+/// An SPI bus shared between several devices
+pub struct SharedSpi<SPI, WORD> {
+    bus: BusLock<SpiBus<SPI, WORD>>,
+}
+
+impl<SPI, WORD> SharedSpi<SPI, WORD> {
+    /// Take ownership of an SPI, so that it can be shared.
+    ///
+    /// A transaction started whilst another is in progress returns
+    /// [`Error::Busy`](Error::Busy).
+    pub fn new(spi: Spi<SPI, WORD>, clocks: &CoreClocks) -> Self {
+        Self::with_lock(spi, clocks, false)
+    }
+
+    /// Take ownership of an SPI, so that it can be shared.
+    ///
+    /// Each transaction runs in a critical section, so it is never
+    /// refused. Interrupts are masked for its duration.
+    pub fn new_critical_section(
+        spi: Spi<SPI, WORD>,
+        clocks: &CoreClocks,
+    ) -> Self {
+        Self::with_lock(spi, clocks, true)
+    }
+
+    fn with_lock(
+        spi: Spi<SPI, WORD>,
+        clocks: &CoreClocks,
+        critical_section: bool,
+    ) -> Self {
+        SharedSpi {
+            bus: BusLock::new(
+                SpiBus {
+                    spi,
+                    clocks: *clocks,
+                    current: None,
+                },
+                critical_section,
+            ),
+        }
+    }
+
+    /// Create a proxy for a device on this bus.
+    ///
+    /// Arguments:
+    /// * `cs` - The chip select pin for this device. It is driven high
+    ///   (de-asserted) immediately.
+    /// * `config` - The SPI configuration for this device.
+    /// * `freq` - The SPI bitrate for this device.
+    pub fn acquire<CS, CONFIG, T>(
+        &self,
+        mut cs: CS,
+        config: CONFIG,
+        freq: T,
+    ) -> SpiProxy<'_, SPI, WORD, CS>
+    where
+        CS: OutputPin<Error = Never>,
+        CONFIG: Into<Config>,
+        T: Into<Hertz>,
+    {
+        cs.set_high().ok();
+
+        SpiProxy {
+            bus: &self.bus,
+            cs,
+            config: config.into(),
+            freq: freq.into().0,
+        }
+    }
+
+    /// Releases the SPI. The proxies borrow the bus, so they must all
+    /// have been dropped.
+    pub fn free(self) -> Spi<SPI, WORD> {
+        self.bus.into_inner().spi
+    }
+}
+
+/// A device on a shared SPI bus
+///
+/// If the bus was not created with
+/// [`new_critical_section`](SharedSpi::new_critical_section), each
+/// transaction returns [`Error::Busy`](Error::Busy) whilst another is in
+/// progress, for example in an interrupt handler that preempted it.
+pub struct SpiProxy<'a, SPI, WORD, CS> {
+    bus: &'a BusLock<SpiBus<SPI, WORD>>,
+    cs: CS,
+    config: Config,
+    freq: u32,
+}
+
+impl<'a, SPI, WORD, CS> SpiProxy<'a, SPI, WORD, CS> {
+    /// Releases the chip select pin
+    pub fn free(self) -> CS {
+        self.cs
+    }
+}
+
+macro_rules! spi_proxy {
+    ($($SPIX:ident: ($($TY:ident),+),)+) => {
+        $(
+            $(
+                impl SpiBus<$SPIX, $TY> {
+                    /// Reconfigure the SPI if the last transaction
+                    /// used a different configuration or bitrate
+                    fn configure(&mut self, config: Config, freq: u32) -> Result<(), spi::Error> {
+                        if self.current != Some((config, freq)) {
+                            self.spi.reconfigure(config, Hertz(freq), &self.clocks)?;
+                            self.current = Some((config, freq));
+                        }
+                        Ok(())
+                    }
+                }
+
+                impl<'a, CS> Transfer<$TY> for SpiProxy<'a, $SPIX, $TY, CS>
+                where
+                    CS: OutputPin<Error = Never>,
+                {
+                    type Error = Error<spi::Error>;
+
+                    fn transfer<'w>(&mut self, words: &'w mut [$TY]) -> Result<&'w [$TY], Self::Error> {
+                        let SpiProxy {
+                            bus,
+                            cs: cs_pin,
+                            config,
+                            freq,
+                        } = self;
+
+                        bus.lock(|bus| {
+                            bus.configure(*config, *freq)?;
+
+                            cs_pin.set_low().ok();
+                            let result = bus.spi.transfer(words).map(|_| ());
+                            cs_pin.set_high().ok();
+
+                            result
+                        })?;
+
+                        Ok(words)
+                    }
+                }
+
+                impl<'a, CS> Write<$TY> for SpiProxy<'a, $SPIX, $TY, CS>
+                where
+                    CS: OutputPin<Error = Never>,
+                {
+                    type Error = Error<spi::Error>;
+
+                    fn write(&mut self, words: &[$TY]) -> Result<(), Self::Error> {
+                        let SpiProxy {
+                            bus,
+                            cs: cs_pin,
+                            config,
+                            freq,
+                        } = self;
+
+                        bus.lock(|bus| {
+                            bus.configure(*config, *freq)?;
+
+                            cs_pin.set_low().ok();
+                            let result = bus.spi.write(words);
+                            cs_pin.set_high().ok();
+
+                            result
+                        })
+                    }
+                }
+            )+
+        )+
+    };
+}
+
+spi_proxy! {
+    SPI1: (u8, u16),
+    SPI2: (u8, u16),
+    SPI3: (u8, u16),
+    SPI4: (u8, u16),
+    SPI5: (u8, u16),
+    SPI6: (u8, u16),
+}
+
+/// An I2C bus shared between several devices
+pub struct SharedI2c<I2C, PINS = ()> {
+    bus: BusLock<I2c<I2C, PINS>>,
+}
+
+impl<I2C, PINS> SharedI2c<I2C, PINS> {
+    /// Take ownership of an I2C, so that it can be shared.
+    ///
+    /// A transaction started whilst another is in progress returns
+    /// [`Error::Busy`](Error::Busy).
+    pub fn new(i2c: I2c<I2C, PINS>) -> Self {
+        SharedI2c {
+            bus: BusLock::new(i2c, false),
+        }
+    }
+
+    /// Take ownership of an I2C, so that it can be shared.
+    ///
+    /// Each transaction runs in a critical section, so it is never
+    /// refused. Interrupts are masked for its duration.
+    pub fn new_critical_section(i2c: I2c<I2C, PINS>) -> Self {
+        SharedI2c {
+            bus: BusLock::new(i2c, true),
+        }
+    }
+
+    /// Create a proxy for a device on this bus.
+    pub fn acquire(&self) -> I2cProxy<'_, I2C, PINS> {
+        I2cProxy { bus: &self.bus }
+    }
+
+    /// Releases the I2C. The proxies borrow the bus, so they must all
+    /// have been dropped.
+    pub fn free(self) -> I2c<I2C, PINS> {
+        self.bus.into_inner()
+    }
+}
+
+/// A device on a shared I2C bus
+///
+/// If the bus was not created with
+/// [`new_critical_section`](SharedI2c::new_critical_section), each
+/// transaction returns [`Error::Busy`](Error::Busy) whilst another is in
+/// progress, for example in an interrupt handler that preempted it.
+pub struct I2cProxy<'a, I2C, PINS = ()> {
+    bus: &'a BusLock<I2c<I2C, PINS>>,
+}
+
+impl<'a, I2C, PINS> I2cWrite for I2cProxy<'a, I2C, PINS>
+where
+    I2c<I2C, PINS>: I2cWrite<Error = i2c::Error>,
+{
+    type Error = Error<i2c::Error>;
+
+    fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.bus.lock(|i2c| i2c.write(addr, bytes))
+    }
+}
+
+impl<'a, I2C, PINS> Read for I2cProxy<'a, I2C, PINS>
+where
+    I2c<I2C, PINS>: Read<Error = i2c::Error>,
+{
+    type Error = Error<i2c::Error>;
+
+    fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.bus.lock(|i2c| i2c.read(addr, buffer))
+    }
+}
+
+impl<'a, I2C, PINS> WriteRead for I2cProxy<'a, I2C, PINS>
+where
+    I2c<I2C, PINS>: WriteRead<Error = i2c::Error>,
+{
+    type Error = Error<i2c::Error>;
+
+    fn write_read(
+        &mut self,
+        addr: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.bus.lock(|i2c| i2c.write_read(addr, bytes, buffer))
+    }
+}
+
+macro_rules! i2c_proxy {
+    ($($I2CX:ident),+) => {
+        $(
+            impl<'a, PINS> I2cProxy<'a, $I2CX, PINS> {
+                /// Write to a 7-bit or 10-bit `address`, see
+                /// [`I2c::write_to`]
+                pub fn write_to(
+                    &mut self,
+                    address: i2c::Address,
+                    bytes: &[u8],
+                ) -> Result<(), Error<i2c::Error>> {
+                    self.bus.lock(|i2c| i2c.write_to(address, bytes))
+                }
+
+                /// Read from a 7-bit or 10-bit `address`, see
+                /// [`I2c::read_from`]
+                pub fn read_from(
+                    &mut self,
+                    address: i2c::Address,
+                    buffer: &mut [u8],
+                ) -> Result<(), Error<i2c::Error>> {
+                    self.bus.lock(|i2c| i2c.read_from(address, buffer))
+                }
+
+                /// Write to and then read from a 7-bit or 10-bit
+                /// `address`, see [`I2c::write_read_from`]
+                pub fn write_read_from(
+                    &mut self,
+                    address: i2c::Address,
+                    bytes: &[u8],
+                    buffer: &mut [u8],
+                ) -> Result<(), Error<i2c::Error>> {
+                    self.bus.lock(|i2c| i2c.write_read_from(address, bytes, buffer))
+                }
+
+                /// Execute a transaction of several operations, see
+                /// [`I2c::transaction`]
+                pub fn transaction(
+                    &mut self,
+                    address: i2c::Address,
+                    operations: &mut [i2c::Operation<'_>],
+                ) -> Result<(), Error<i2c::Error>> {
+                    self.bus.lock(|i2c| i2c.transaction(address, operations))
+                }
+            }
+        )+
+    };
+}
+
+i2c_proxy!(I2C1, I2C2, I2C3, I2C4);
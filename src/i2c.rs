@@ -1,8 +1,136 @@
 //! Inter Integrated Circuit (I2C)
+//!
+//! This module implements the [embedded-hal](embedded-hal) blocking I2C
+//! traits for master mode.
+//!
+//! The embedded-hal traits use 7-bit addresses. Slaves with 10-bit
+//! addresses can be accessed with the [`write_to`](I2c::write_to),
+//! [`read_from`](I2c::read_from) and
+//! [`write_read_from`](I2c::write_read_from) methods:
+//!
+//! ```
+//! i2c.write_read_from(i2c::Address::TenBit(0x2A5), &[0x01], &mut buffer)?;
+//! ```
+//!
+//! Other combinations of reads and writes, joined by repeated STARTs,
+//! can be executed with [`transaction`](I2c::transaction).
+//!
+//! # Interrupt-driven Transactions
+//!
+//! For use where blocking is not acceptable, the I2C can be converted
+//! to an [`InterruptI2c`], which processes a transaction from the I2C
+//! event and error interrupts. The buffers must have a `'static`
+//! lifetime, and are returned when the transaction completes.
+//!
+//! Transactions are not queued. Only one transaction can be in flight,
+//! and [`submit`](InterruptI2c::submit) returns [`Busy`] with the
+//! transaction until the previous one has been collected with
+//! [`take`](InterruptI2c::take).
+//!
+//! ```
+//! let mut i2c = i2c.interrupt_driven();
+//! i2c.submit(i2c::Transaction::WriteRead {
+//!     address: i2c::Address::SevenBit(0x40),
+//!     bytes: &COMMAND,
+//!     buffer: unsafe { &mut BUFFER },
+//! }).ok();
+//!
+//! // In both the I2C1_EV and I2C1_ER interrupts
+//! if let Some(result) = i2c.on_interrupt() {
+//!     let (transaction, result) = i2c.take().unwrap();
+//!     ...
+//! }
+//! ```
+//!
+//! # Bus Timing
+//!
+//! The bus timing is calculated to meet the I2C specification for the
+//! given bus frequency. The rise and fall times and noise filters can
+//! be specified with a [`Config`]:
+//!
+//! ```
+//! let config = i2c::Config::new(400.khz()).rise_time(250).fall_time(20);
+//! let i2c = dp.I2C1.i2c((scl, sda), config, ccdr.peripheral.I2C1, &ccdr.clocks, &mut dp.SYSCFG)?;
+//! ```
+//!
+//! # Errors and Bus Recovery
+//!
+//! Each wait in master mode is bounded by a timeout, which can be set
+//! with [`set_timeout`](I2c::set_timeout). Bus errors, arbitration
+//! loss, NACKs and timeouts are reported as an [`Error`]. After a
+//! timeout the peripheral is reset.
+//!
+//! If a slave is left holding SDA low, for example after a reset in
+//! the middle of a transfer, the bus can be recovered with [`recover`]
+//! by temporarily using the pins as GPIOs. The I2C is then created
+//! again, which resets the peripheral:
+//!
+//! ```
+//! let (i2c1, prec, (scl, sda)) = i2c.free();
+//! let mut scl = scl.into_open_drain_output();
+//! let mut sda = sda.into_open_drain_output();
+//! i2c::recover(&mut scl, &mut sda, &mut delay)?;
+//!
+//! let pins = (scl.into_alternate_af4(), sda.into_alternate_af4());
+//! let i2c = i2c1.i2c(pins, 100.khz(), prec, &ccdr.clocks, &mut dp.SYSCFG)?;
+//! ```
+//!
+//! # SMBus and PMBus
+//!
+//! SMBus host or device mode is enabled with
+//! [`enable_smbus`](I2c::enable_smbus), optionally with hardware
+//! packet error checking (PEC). The SMBus commands are then available
+//! as methods. When PEC is enabled, a PEC byte is appended to each
+//! message sent, and checked on each message received.
+//!
+//! ```
+//! i2c.enable_smbus(i2c::SmbusMode::Host, true);
+//! i2c.smbus_timeout(25.ms(), Some(10.ms()))?;
+//!
+//! let vout = i2c.smbus_read_word(0x40, 0x8B)?;  // PMBus READ_VOUT
+//! ```
+//!
+//! The SMBA pin used for SMBus alerts must be configured by the user.
+//!
+//! # Slave Mode
+//!
+//! The I2C can also respond as a slave to one or two own addresses,
+//! and optionally the general call address. Own address 2 can be
+//! masked so that it matches a range of addresses. Slave transfers
+//! are driven by polling [`slave_poll`](I2c::slave_poll), or by
+//! calling it from the I2C event interrupt:
+//!
+//! ```
+//! i2c.enable_own_address1(0x42);
+//!
+//! loop {
+//!     match block!(i2c.slave_poll())? {
+//!         i2c::SlaveEvent::AddressMatch { direction, .. } => { ... }
+//!         i2c::SlaveEvent::Received(byte) => { ... }
+//!         i2c::SlaveEvent::TransmitRequest => i2c.slave_write(0x55),
+//!         i2c::SlaveEvent::Stop => { ... }
+//!     }
+//! }
+//! ```
+//!
+//! ## Wakeup from Stop
+//!
+//! The I2C can wakeup the device from Stop mode when an own address is
+//! matched. The I2C kernel clock must be HSI or CSI, and the digital
+//! noise filter disabled, see [`wakeup_from_stop`](I2c::wakeup_from_stop).
+//!
+//! ```
+//! i2c.enable_own_address1(0x42);
+//! i2c.listen(i2c::Event::AddressMatch);
+//! i2c.wakeup_from_stop(true)?;
+//! exti.listen(exti::Event::I2C1);
+//! ```
 
 use core::cmp;
 use core::marker::PhantomData;
 
+use cortex_m::interrupt;
+
 use crate::gpio::gpioa::PA8;
 use crate::gpio::gpiob::{PB10, PB11, PB6, PB7, PB8, PB9};
 use crate::gpio::gpioc::PC9;
@@ -10,14 +138,17 @@ use crate::gpio::gpiod::{PD12, PD13};
 use crate::gpio::gpiof::{PF0, PF1, PF14, PF15};
 use crate::gpio::gpioh::{PH11, PH12, PH4, PH5, PH7, PH8};
 use crate::gpio::{Alternate, AF4, AF6};
+use crate::hal::blocking::delay::DelayUs;
 use crate::hal::blocking::i2c::{Read, Write, WriteRead};
+use crate::hal::digital::v2::{InputPin, OutputPin};
+use crate::rcc::rec::{I2c123ClkSel, I2c123ClkSelGetter, I2c4ClkSel};
 use crate::rcc::{rec, CoreClocks, ResetEnable};
-use crate::stm32::{I2C1, I2C2, I2C3, I2C4};
-use crate::time::Hertz;
+use crate::stm32::{I2C1, I2C2, I2C3, I2C4, SYSCFG};
+use crate::time::{Hertz, KiloHertz, MegaHertz, MilliSeconds};
 use cast::u16;
 
 /// I2C error
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Error {
     /// Bus error
     Bus,
@@ -25,14 +156,404 @@ pub enum Error {
     Arbitration,
     /// No ack received
     NotAcknowledge,
-    // Overrun, // slave mode only
-    // Pec, // SMBUS mode only
-    // Timeout, // SMBUS mode only
-    // Alert, // SMBUS mode only
+    /// Overrun or underrun in slave mode
+    Overrun,
+    /// Received PEC does not match, in SMBus mode
+    Pec,
+    /// The bus did not respond in time, or an SMBus timeout was
+    /// detected
+    Timeout,
+    /// Wakeup from Stop is not possible with the digital noise filter
+    /// enabled
+    DigitalFilter,
+    /// Wakeup from Stop requires the I2C kernel clock to be HSI or CSI
+    WakeupClock,
+    /// SMBus block byte count out of range, or larger than the buffer
+    BlockSize,
+    /// SMBus timeout is zero, or too long for the I2C kernel clock
+    TimeoutRange,
     #[doc(hidden)]
     _Extensible,
 }
 
+/// Interrupt events
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    /// Transmit data register empty (TXIS)
+    Transmit,
+    /// Receive data register not empty (RXNE)
+    Receive,
+    /// Transfer complete (TC and TCR)
+    TransferComplete,
+    /// STOP condition detected (STOPF)
+    Stop,
+    /// Not acknowledge received (NACKF)
+    NotAcknowledge,
+    /// Own address matched in slave mode (ADDR)
+    AddressMatch,
+    /// Bus error, arbitration loss, overrun, PEC error, timeout or alert
+    Errors,
+}
+
+/// SMBus mode
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SmbusMode {
+    /// SMBus host. The SMBus host address (0x08) is also acknowledged
+    /// in slave mode
+    Host,
+    /// SMBus device. The SMBus device default address (0x61) is also
+    /// acknowledged in slave mode
+    Device,
+}
+
+/// A segment of a transaction, see [`I2c::transaction`]
+#[derive(Debug, PartialEq)]
+pub enum Operation<'a> {
+    /// Read into the buffer
+    Read(&'a mut [u8]),
+    /// Write the bytes
+    Write(&'a [u8]),
+}
+
+impl<'a> Operation<'a> {
+    fn direction(&self) -> Direction {
+        match self {
+            Operation::Read(_) => Direction::Read,
+            Operation::Write(_) => Direction::Write,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Operation::Read(buffer) => buffer.len(),
+            Operation::Write(bytes) => bytes.len(),
+        }
+    }
+}
+
+/// A slave address
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Address {
+    /// 7-bit address
+    SevenBit(u8),
+    /// 10-bit address
+    TenBit(u16),
+}
+
+/// Direction of a transfer, as seen by the master
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+    /// The master writes to the slave
+    Write,
+    /// The master reads from the slave
+    Read,
+}
+
+/// Events in slave mode
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SlaveEvent {
+    /// An own address, or the general call address (0), was matched
+    AddressMatch { address: u8, direction: Direction },
+    /// A byte was received from the master
+    Received(u8),
+    /// The master requests a byte. Reply with
+    /// [`slave_write`](I2c::slave_write)
+    TransmitRequest,
+    /// A STOP condition was detected
+    Stop,
+}
+
+/// I2C bus configuration
+///
+/// The bus timing is calculated from the bus frequency, the rise and
+/// fall times of SCL and SDA, and the noise filters, such that the
+/// requirements of the I2C specification are met. Rise and fall times
+/// depend on the bus capacitance and pull-up resistors, and should be
+/// measured on the board.
+///
+/// A frequency can be used where a `Config` is expected, in which case
+/// the defaults below are used.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Config {
+    frequency: u32,
+    rise_time: u32,
+    fall_time: u32,
+    analog_filter: bool,
+    digital_filter: u8,
+}
+
+impl Config {
+    /// Create a default configuration for the bus `frequency`. The
+    /// rise time is 100ns, the fall time is 10ns, the analog filter is
+    /// enabled and the digital filter is disabled.
+    pub fn new<F>(frequency: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        Config {
+            frequency: frequency.into().0,
+            rise_time: 100,
+            fall_time: 10,
+            analog_filter: true,
+            digital_filter: 0,
+        }
+    }
+
+    /// SCL and SDA rise time (tr) in nanoseconds
+    pub fn rise_time(mut self, ns: u32) -> Self {
+        self.rise_time = ns;
+        self
+    }
+
+    /// SCL and SDA fall time (tf) in nanoseconds
+    pub fn fall_time(mut self, ns: u32) -> Self {
+        self.fall_time = ns;
+        self
+    }
+
+    /// Enable or disable the analog noise filter, which suppresses
+    /// spikes shorter than 50ns
+    ///
+    /// In Fast-mode Plus, the delay of the analog filter may prevent
+    /// the data valid time being met with a slow I2C kernel clock.
+    pub fn analog_filter(mut self, enable: bool) -> Self {
+        self.analog_filter = enable;
+        self
+    }
+
+    /// Suppress spikes shorter than `cycles` I2C kernel clock cycles
+    /// with the digital noise filter. Zero disables the filter.
+    ///
+    /// The digital filter must be disabled to use
+    /// [`wakeup_from_stop`](I2c::wakeup_from_stop).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cycles` is larger than 15
+    pub fn digital_filter(mut self, cycles: u8) -> Self {
+        assert!(cycles <= 15);
+        self.digital_filter = cycles;
+        self
+    }
+
+    /// Calculate the bus timing for the I2C kernel clock `i2cclk`.
+    ///
+    /// The timing with the smallest prescaler is chosen. The resulting
+    /// bus frequency is never higher than the specified frequency, but
+    /// may be slightly lower if the rise and fall times are long.
+    pub fn timing(&self, i2cclk: Hertz) -> Result<Timing, TimingError> {
+        calculate_timing(self, i2cclk.0)
+    }
+}
+
+impl From<Hertz> for Config {
+    fn from(frequency: Hertz) -> Self {
+        Config::new(frequency)
+    }
+}
+
+impl From<KiloHertz> for Config {
+    fn from(frequency: KiloHertz) -> Self {
+        Config::new(frequency)
+    }
+}
+
+impl From<MegaHertz> for Config {
+    fn from(frequency: MegaHertz) -> Self {
+        Config::new(frequency)
+    }
+}
+
+/// Bus timing, as programmed into the TIMINGR register
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Timing {
+    /// Timing prescaler
+    pub presc: u8,
+    /// SCL low period, in prescaled clock cycles minus one
+    pub scll: u8,
+    /// SCL high period, in prescaled clock cycles minus one
+    pub sclh: u8,
+    /// Data hold time, in prescaled clock cycles
+    pub sdadel: u8,
+    /// Data setup time, in prescaled clock cycles minus one
+    pub scldel: u8,
+    /// Fast-mode Plus drive is required
+    pub fast_mode_plus: bool,
+}
+
+/// Errors from the bus timing calculation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TimingError {
+    /// The bus frequency is zero or higher than 1MHz
+    InvalidFrequency,
+    /// The rise or fall time is longer than allowed for the bus
+    /// frequency
+    RiseFallTime,
+    /// The I2C kernel clock is too slow for the bus frequency
+    ClockTooSlow,
+    /// The I2C kernel clock is too fast for the bus frequency
+    ClockTooFast,
+    /// The data hold and setup times cannot be met. Try shorter rise
+    /// and fall times, or less filtering
+    DataTiming,
+    /// The I2C kernel clock is not running
+    KernelClock,
+}
+
+/// Timing requirements of the I2C specification (UM10204 Table 10) for
+/// each mode, in nanoseconds
+struct Spec {
+    t_low_min: i64,
+    t_high_min: i64,
+    t_su_dat_min: i64,
+    t_vd_dat_max: i64,
+    t_r_max: u32,
+    t_f_max: u32,
+}
+
+const STANDARD_MODE: Spec = Spec {
+    t_low_min: 4700,
+    t_high_min: 4000,
+    t_su_dat_min: 250,
+    t_vd_dat_max: 3450,
+    t_r_max: 1000,
+    t_f_max: 300,
+};
+const FAST_MODE: Spec = Spec {
+    t_low_min: 1300,
+    t_high_min: 600,
+    t_su_dat_min: 100,
+    t_vd_dat_max: 900,
+    t_r_max: 300,
+    t_f_max: 300,
+};
+const FAST_MODE_PLUS: Spec = Spec {
+    t_low_min: 500,
+    t_high_min: 260,
+    t_su_dat_min: 50,
+    t_vd_dat_max: 450,
+    t_r_max: 120,
+    t_f_max: 120,
+};
+
+/// Divide, rounding up. `d` must be positive
+fn div_ceil(n: i64, d: i64) -> i64 {
+    if n > 0 {
+        (n + d - 1) / d
+    } else {
+        n / d
+    }
+}
+
+/// Calculate the TIMINGR fields for `config`, with an I2C kernel clock
+/// of `i2cclk` Hz. Refer to RM0433 Rev 7 Section 47.4.9.
+///
+/// All times are in picoseconds.
+fn calculate_timing(
+    config: &Config,
+    i2cclk: u32,
+) -> Result<Timing, TimingError> {
+    const PS: i64 = 1000; // picoseconds per nanosecond
+
+    let freq = config.frequency;
+    let spec = match freq {
+        1..=100_000 => &STANDARD_MODE,
+        100_001..=400_000 => &FAST_MODE,
+        400_001..=1_000_000 => &FAST_MODE_PLUS,
+        _ => return Err(TimingError::InvalidFrequency),
+    };
+    if config.rise_time > spec.t_r_max || config.fall_time > spec.t_f_max {
+        return Err(TimingError::RiseFallTime);
+    }
+    if i2cclk == 0 {
+        return Err(TimingError::ClockTooSlow);
+    }
+
+    let t_i2cclk = 1_000_000_000_000 / i64::from(i2cclk);
+    let t_scl = 1_000_000_000_000 / i64::from(freq);
+    let t_r = i64::from(config.rise_time) * PS;
+    let t_f = i64::from(config.fall_time) * PS;
+    let dnf = i64::from(config.digital_filter);
+
+    // Analog filter delay
+    let (t_af_min, t_af_max) = if config.analog_filter {
+        (50 * PS, 260 * PS)
+    } else {
+        (0, 0)
+    };
+
+    // The kernel clock must be fast enough to sample SCL
+    let t_filters = t_af_min + dnf * t_i2cclk;
+    if 4 * t_i2cclk >= spec.t_low_min * PS - t_filters
+        || t_i2cclk >= spec.t_high_min * PS
+    {
+        return Err(TimingError::ClockTooSlow);
+    }
+
+    // Delay between SCL reaching the threshold and the peripheral
+    // detecting it
+    let t_sync = t_af_min + (dnf + 2) * t_i2cclk;
+
+    // Data hold time window. tHD;DAT(min) is zero
+    let sdadel_min = t_f - t_af_min - (dnf + 3) * t_i2cclk;
+    let sdadel_max =
+        spec.t_vd_dat_max * PS - t_r - t_af_max - (dnf + 4) * t_i2cclk;
+    if sdadel_max < 0 {
+        return Err(TimingError::DataTiming);
+    }
+
+    let mut error = TimingError::ClockTooFast;
+    for presc in 0..16 {
+        let t_presc = (presc + 1) * t_i2cclk;
+
+        // Data hold time
+        let sdadel = cmp::max(div_ceil(sdadel_min, t_presc), 0);
+        if sdadel * t_presc > sdadel_max {
+            error = TimingError::DataTiming;
+            continue;
+        }
+
+        // Data setup time
+        let scldel = div_ceil(t_r + spec.t_su_dat_min * PS, t_presc) - 1;
+
+        // Minimum SCL low and high periods
+        let scll_min = div_ceil(spec.t_low_min * PS - t_sync, t_presc) - 1;
+        let sclh_min = div_ceil(spec.t_high_min * PS - t_sync, t_presc) - 1;
+        let scll_min = cmp::max(scll_min, 0);
+        let sclh_min = cmp::max(sclh_min, 0);
+
+        // Prescaled cycles in a bus period, rounding up so that the
+        // frequency is not too high. Any cycles left over after the
+        // minimum low and high periods are shared between them
+        let cycles = div_ceil(t_scl - t_r - t_f - 2 * t_sync, t_presc);
+        let spare = cmp::max(cycles - (scll_min + 1) - (sclh_min + 1), 0);
+        let scll = scll_min + (spare + 1) / 2;
+        let sclh = sclh_min + spare / 2;
+
+        if sdadel > 15 || scldel > 15 || scll > 255 || sclh > 255 {
+            error = TimingError::ClockTooFast;
+            continue;
+        }
+
+        // The data must change and settle whilst SCL is low
+        if (sdadel + scldel + 1) > (scll + 1) {
+            error = TimingError::DataTiming;
+            continue;
+        }
+
+        return Ok(Timing {
+            presc: presc as u8,
+            scll: scll as u8,
+            sclh: sclh as u8,
+            sdadel: sdadel as u8,
+            scldel: scldel as u8,
+            fast_mode_plus: freq > 400_000,
+        });
+    }
+
+    Err(error)
+}
+
 /// A trait to represent the SCL Pin of an I2C Port
 pub trait PinScl<I2C> {
     fn set_open_drain(self) -> Self;
@@ -61,34 +582,164 @@ where
 }
 
 #[derive(Debug)]
-pub struct I2c<I2C> {
+pub struct I2c<I2C, PINS = ()> {
     i2c: I2C,
+    /// The SCL and SDA pins, or `()` if created without pins
+    pins: PINS,
+    /// Maximum number of ISR polls for each wait
+    timeout: u32,
+    /// I2C kernel clock frequency
+    ker_ck: Hertz,
+    /// The I2C kernel clock can wakeup from Stop mode
+    ker_ck_wakeup: bool,
+}
+
+impl<I2C> I2c<I2C> {
+    /// Keep `pins`, so that they are returned by `free`
+    fn with_pins<PINS>(self, pins: PINS) -> I2c<I2C, PINS> {
+        I2c {
+            i2c: self.i2c,
+            pins,
+            timeout: self.timeout,
+            ker_ck: self.ker_ck,
+            ker_ck_wakeup: self.ker_ck_wakeup,
+        }
+    }
+}
+
+/// A transaction for [`InterruptI2c`]
+#[derive(Debug)]
+pub enum Transaction {
+    /// Write `bytes` to the slave
+    Write {
+        address: Address,
+        bytes: &'static [u8],
+    },
+    /// Read into `buffer` from the slave
+    Read {
+        address: Address,
+        buffer: &'static mut [u8],
+    },
+    /// Write `bytes` to the slave, then read into `buffer` after a
+    /// repeated START
+    WriteRead {
+        address: Address,
+        bytes: &'static [u8],
+        buffer: &'static mut [u8],
+    },
+}
+
+/// Returned by [`submit`](InterruptI2c::submit) when another
+/// transaction is in flight, or has not been collected with
+/// [`take`](InterruptI2c::take). Contains the transaction that was not
+/// started.
+#[derive(Debug)]
+pub struct Busy(pub Transaction);
+
+/// An I2C master driven by the I2C event and error interrupts
+///
+/// Only one transaction can be in flight at a time.
+#[derive(Debug)]
+pub struct InterruptI2c<I2C, PINS = ()> {
+    i2c: I2c<I2C, PINS>,
+    transaction: Option<Transaction>,
+    /// Direction of the current part of the transaction
+    direction: Direction,
+    /// Bytes transferred in the current part of the transaction
+    index: usize,
+    /// Error waiting for the STOP condition
+    error: Option<Error>,
+    /// Result of the completed transaction
+    result: Option<Result<(), Error>>,
 }
 
 pub trait I2cExt<I2C>: Sized {
     type Rec: ResetEnable;
 
-    fn i2c<PINS, F>(
+    fn i2c<PINS, C>(
         self,
-        _pins: PINS,
-        frequency: F,
+        pins: PINS,
+        config: C,
         prec: Self::Rec,
         clocks: &CoreClocks,
-    ) -> I2c<I2C>
+        syscfg: &mut SYSCFG,
+    ) -> Result<I2c<I2C, PINS>, TimingError>
     where
         PINS: Pins<I2C>,
-        F: Into<Hertz>;
+        C: Into<Config>;
 
-    fn i2c_unchecked<F>(
+    fn i2c_unchecked<C>(
         self,
-        frequency: F,
+        config: C,
         prec: Self::Rec,
         clocks: &CoreClocks,
-    ) -> I2c<I2C>
+        syscfg: &mut SYSCFG,
+    ) -> Result<I2c<I2C>, TimingError>
     where
-        F: Into<Hertz>;
+        C: Into<Config>;
+}
+
+trait KerClk {
+    type Rec;
+
+    /// Return the frequency of the I2C kernel clock, if it is running
+    fn kernel_clk(prec: &Self::Rec, clocks: &CoreClocks) -> Option<Hertz>;
+
+    /// Return `true` if the I2C kernel clock can wakeup from Stop mode
+    fn kernel_clk_wakeup(prec: &Self::Rec) -> bool;
 }
 
+macro_rules! i2c123_kernel_clk {
+    ($($I2CX:ident: $Rec:ident),+) => {
+        $(
+            impl KerClk for $I2CX {
+                type Rec = rec::$Rec;
+
+                fn kernel_clk(prec: &rec::$Rec, clocks: &CoreClocks) -> Option<Hertz> {
+                    match prec.get_kernel_clk_mux() {
+                        I2c123ClkSel::RCC_PCLK1 => Some(clocks.pclk1()),
+                        I2c123ClkSel::PLL3_R => clocks.pll3_r_ck(),
+                        I2c123ClkSel::HSI_KER => clocks.hsi_ck(),
+                        I2c123ClkSel::CSI_KER => clocks.csi_ck(),
+                    }
+                }
+
+                fn kernel_clk_wakeup(prec: &rec::$Rec) -> bool {
+                    matches!(
+                        prec.get_kernel_clk_mux(),
+                        I2c123ClkSel::HSI_KER | I2c123ClkSel::CSI_KER
+                    )
+                }
+            }
+        )+
+    };
+}
+
+i2c123_kernel_clk!(I2C1: I2c1, I2C2: I2c2, I2C3: I2c3);
+
+impl KerClk for I2C4 {
+    type Rec = rec::I2c4;
+
+    fn kernel_clk(prec: &rec::I2c4, clocks: &CoreClocks) -> Option<Hertz> {
+        match prec.get_kernel_clk_mux() {
+            I2c4ClkSel::RCC_PCLK4 => Some(clocks.pclk4()),
+            I2c4ClkSel::PLL3_R => clocks.pll3_r_ck(),
+            I2c4ClkSel::HSI_KER => clocks.hsi_ck(),
+            I2c4ClkSel::CSI_KER => clocks.csi_ck(),
+        }
+    }
+
+    fn kernel_clk_wakeup(prec: &rec::I2c4) -> bool {
+        matches!(
+            prec.get_kernel_clk_mux(),
+            I2c4ClkSel::HSI_KER | I2c4ClkSel::CSI_KER
+        )
+    }
+}
+
+/// Default timeout for each wait in master mode
+const DEFAULT_TIMEOUT: MilliSeconds = MilliSeconds(10);
+
 // Sequence to flush the TXDR register. This resets the TXIS and TXE
 // flags
 macro_rules! flush_txdr {
@@ -105,385 +756,1298 @@ macro_rules! flush_txdr {
     };
 }
 
+// Software reset. Clearing PE releases SCL and SDA, and resets the
+// state machine and status flags. The configuration is kept. PE must
+// be low for at least 3 APB cycles, which is ensured by reading it back
+macro_rules! software_reset {
+    ($i2c:expr) => {
+        $i2c.cr1.modify(|_, w| w.pe().clear_bit());
+        while $i2c.cr1.read().pe().bit_is_set() {}
+        $i2c.cr1.modify(|_, w| w.pe().set_bit());
+    };
+}
+
+// Wait for `$flag`, polling ISR at most `$timeout` times. Errors are
+// checked before the flag, so that they are reported by the wait where
+// they occur
 macro_rules! busy_wait {
-    ($i2c:expr, $flag:ident, $variant:ident) => {
+    ($i2c:expr, $flag:ident, $variant:ident, $timeout:expr) => {
+        let mut polls: u32 = $timeout;
+
         loop {
             let isr = $i2c.isr.read();
 
-            if isr.$flag().$variant() {
-                break;
-            } else if isr.berr().is_error() {
+            if isr.berr().is_error() {
                 $i2c.icr.write(|w| w.berrcf().set_bit());
+                flush_txdr!($i2c);
                 return Err(Error::Bus);
             } else if isr.arlo().is_lost() {
                 $i2c.icr.write(|w| w.arlocf().set_bit());
+                flush_txdr!($i2c);
                 return Err(Error::Arbitration);
             } else if isr.nackf().bit_is_set() {
                 $i2c.icr.write(|w| w.stopcf().set_bit().nackcf().set_bit());
                 flush_txdr!($i2c);
                 return Err(Error::NotAcknowledge);
+            } else if isr.pecerr().is_no_match() {
+                $i2c.icr.write(|w| w.peccf().set_bit());
+                return Err(Error::Pec);
+            } else if isr.timeout().is_timeout() {
+                $i2c.icr.write(|w| w.timoutcf().set_bit());
+                return Err(Error::Timeout);
+            } else if isr.$flag().$variant() {
+                break;
+            } else if polls == 0 {
+                // The bus is stuck, or a slave is stretching the clock
+                // for too long
+                software_reset!($i2c);
+                return Err(Error::Timeout);
             } else {
-                // try again
+                polls -= 1;
             }
         }
     };
 }
 
+/// NBYTES and RELOAD for a transfer with `remaining` bytes left. At
+/// most 255 bytes can be transferred before NBYTES must be reloaded
+fn nbytes_reload(remaining: usize) -> (u8, bool) {
+    if remaining > 255 {
+        (255, true)
+    } else {
+        (remaining as u8, false)
+    }
+}
+
+/// NBYTES for the `count` data bytes of an SMBus block, followed by
+/// the PEC byte if `pec`. The whole block must fit in a single NBYTES
+/// transfer, since PECBYTE cannot be set together with RELOAD
+fn smbus_block_nbytes(count: usize, pec: bool) -> Option<u8> {
+    match count + pec as usize {
+        0 => None,
+        n if n > 255 => None,
+        n => Some(n as u8),
+    }
+}
+
+/// Number of ISR polls for a timeout of at least `timeout`. Each poll
+/// takes at least one cycle of the peripheral bus clock `pclk`
+fn timeout_polls(timeout: MilliSeconds, pclk: u32) -> u32 {
+    (pclk / 1000).saturating_mul(timeout.0)
+}
+
+/// TIMEOUTA or TIMEOUTB value for a timeout of at least `timeout`,
+/// counted in units of 2048 I2C kernel clock cycles. Returns `None` if
+/// the timeout is zero or does not fit in 12 bits
+fn timeout_ticks(timeout: MilliSeconds, i2cclk: u32) -> Option<u16> {
+    // At most 2^22 * 2^32 cycles, which cannot overflow
+    let cycles = i64::from(i2cclk / 1000) * i64::from(timeout.0);
+    let ticks = div_ceil(cycles, 2048);
+
+    match ticks {
+        1..=0x1000 => Some((ticks - 1) as u16),
+        _ => None,
+    }
+}
+
+/// Recover a bus where a slave is holding SDA low, for example after a
+/// reset in the middle of a transfer.
+///
+/// `scl` and `sda` must be configured as open-drain GPIO outputs, so
+/// the I2C must first be released with `free`. SCL is clocked up to 9
+/// times until the slave releases SDA, and then a STOP condition is
+/// generated. Afterwards, the pins should be configured for I2C again
+/// and the I2C created again, which resets the peripheral.
+///
+/// Returns [`Error::Bus`](Error::Bus) if SDA is still held low.
+pub fn recover<SCL, SDA, D>(
+    scl: &mut SCL,
+    sda: &mut SDA,
+    delay: &mut D,
+) -> Result<(), Error>
+where
+    SCL: OutputPin,
+    SDA: OutputPin + InputPin,
+    D: DelayUs<u32>,
+{
+    // Half a bit time at 100kHz
+    const HALF_PERIOD: u32 = 5;
+
+    scl.set_high().ok();
+    sda.set_high().ok();
+    delay.delay_us(HALF_PERIOD);
+
+    for _ in 0..9 {
+        if sda.is_high().unwrap_or(false) {
+            break;
+        }
+        scl.set_low().ok();
+        delay.delay_us(HALF_PERIOD);
+        scl.set_high().ok();
+        delay.delay_us(HALF_PERIOD);
+    }
+
+    // STOP: SDA rises whilst SCL is high
+    scl.set_low().ok();
+    sda.set_low().ok();
+    delay.delay_us(HALF_PERIOD);
+    scl.set_high().ok();
+    delay.delay_us(HALF_PERIOD);
+    sda.set_high().ok();
+    delay.delay_us(HALF_PERIOD);
+
+    if sda.is_high().unwrap_or(false) {
+        Ok(())
+    } else {
+        Err(Error::Bus)
+    }
+}
+
 macro_rules! i2c {
-    ($($I2CX:ident: ($i2cX:ident, $Rec:ident, $pclkX:ident),)+) => {
+    ($($I2CX:ident: ($i2cX:ident, $Rec:ident, $pclkX:ident, $fmp:ident),)+) => {
         $(
             impl I2c<$I2CX> {
                 /// Create and initialise a new I2C peripheral.
                 ///
-                /// The frequency of the I2C bus clock is specified by `frequency`.
+                /// The bus frequency and timing is specified by
+                /// `config`, which may simply be a frequency. Above
+                /// 400kHz, Fast-mode Plus drive is enabled in `syscfg`.
                 ///
-                /// # Panics
-                ///
-                /// Panics if the ratio between `frequency` and the i2c_ker_ck
-                /// is out of bounds. The acceptable range is [4, 8192].
-                ///
-                /// Panics if the `frequency` is too fast. The maximum is 1MHz.
-                pub fn $i2cX<F> (
+                /// Returns a [`TimingError`] if the I2C kernel clock is
+                /// not running, or the bus timing cannot be met with it.
+                pub fn $i2cX<C> (
                     i2c: $I2CX,
-                    frequency: F,
+                    config: C,
                     prec: rec::$Rec,
-                    clocks: &CoreClocks
-                ) -> Self where
-                    F: Into<Hertz>,
+                    clocks: &CoreClocks,
+                    syscfg: &mut SYSCFG,
+                ) -> Result<Self, TimingError> where
+                    C: Into<Config>,
                 {
-                    prec.enable().reset();
-
-                    let freq = frequency.into().0;
+                    let prec = prec.enable().reset();
 
-                    assert!(freq <= 1_000_000);
-
-                    let i2cclk = clocks.$pclkX().0;
+                    let config = config.into();
+                    let i2cclk = $I2CX::kernel_clk(&prec, clocks)
+                        .ok_or(TimingError::KernelClock)?
+                        .0;
+                    let timing = config.timing(Hertz(i2cclk))?;
 
                     // Clear PE bit in I2C_CR1
                     i2c.cr1.modify(|_, w| w.pe().clear_bit());
 
-                    // Enable the Analog Noise Filter by setting
-                    // ANFOFF (Analog Noise Filter OFF) to 0.  This is
-                    // usually enabled by default
-                    i2c.cr1.modify(|_, w| w.anfoff().clear_bit());
-
-                    // Refer to RM0433 Rev 6 - Figure 539 for setup and hold timing:
-                    //
-                    // TODO review SDADEL and SCLDEL compliance with the
-                    // peripheral timing requirements
-                    //
-                    // t_I2CCLK = 1 / PCLK1
-                    // t_PRESC  = (PRESC + 1) * t_I2CCLK
-                    // t_SCLL   = (SCLL + 1) * t_PRESC
-                    // t_SCLH   = (SCLH + 1) * t_PRESC
-                    //
-                    // t_SYNC1 + t_SYNC2 > 4 * t_I2CCLK
-                    // t_SCL ~= t_SYNC1 + t_SYNC2 + t_SCLL + t_SCLH
-                    let ratio = i2cclk / freq;
-
-                    // For the standard-mode configuration method, we must have
-                    // a ratio of 4 or higher
-                    assert!(ratio >= 4, "The I2C PCLK must be at least 4 times the bus frequency!");
-
-                    let (presc_reg, scll, sclh, sdadel, scldel) = if freq > 100_000 {
-                        // fast-mode or fast-mode plus
-                        // here we pick SCLL + 1 = 2 * (SCLH + 1)
-
-                        // Prescaler, 384 ticks for sclh/scll. Round up then
-                        // subtract 1
-                        let presc_reg = ((ratio - 1) / 384) as u8;
-                        // ratio < 1200 by pclk 120MHz max., therefore presc < 16
-
-                        // Actual precale value selected
-                        let presc = (presc_reg + 1) as u32;
-
-                        let sclh = ((ratio / presc) - 3) / 3;
-                        let scll = 2 * (sclh + 1);
-
-                        let (sdadel, scldel) = if freq > 400_000 {
-                            // fast-mode plus
-                            let sdadel = 0;
-                            let scldel = i2cclk / 4_000_000 / presc - 1;
-
-                            (sdadel, scldel)
-                        } else {
-                            // fast-mode
-                            let sdadel = i2cclk / 8_000_000 / presc;
-                            let scldel = i2cclk / 2_000_000 / presc - 1;
-
-                            (sdadel, scldel)
-                        };
-
-                        (presc_reg, scll as u8, sclh as u8, sdadel as u8, scldel as u8)
-                    } else {
-                        // standard-mode
-                        // here we pick SCLL = SCLH
-
-                        // Prescaler, 512 ticks for sclh/scll. Round up then
-                        // subtract 1
-                        let presc = (ratio - 1) / 512;
-                        let presc_reg = cmp::min(presc, 15) as u8;
-
-                        // Actual prescale value selected
-                        let presc = (presc_reg + 1) as u32;
-
-                        let sclh = ((ratio / presc) - 2) / 2;
-                        let scll = sclh;
-
-                        // Speed check
-                        assert!(sclh < 256, "The I2C PCLK is too fast for this bus frequency!");
-
-                        let sdadel = i2cclk / 2_000_000 / presc;
-                        let scldel = i2cclk / 800_000 / presc - 1;
-
-                        (presc_reg, scll as u8, sclh as u8, sdadel as u8, scldel as u8)
-                    };
-
-                    // Sanity check
-                    assert!(presc_reg < 16);
+                    // Noise filters. These can only be changed whilst
+                    // PE is clear
+                    i2c.cr1.modify(|_, w| {
+                        w.anfoff()
+                            .bit(!config.analog_filter)
+                            .dnf()
+                            .bits(config.digital_filter)
+                    });
 
-                    // Keep values within reasonable limits for fast per_ck
-                    let sdadel = cmp::max(sdadel, 2);
-                    let scldel = cmp::max(scldel, 4);
+                    // Fast-mode Plus drive. PMCR is shared with other
+                    // peripherals
+                    interrupt::free(|_| {
+                        syscfg.pmcr.modify(|_, w| w.$fmp().bit(timing.fast_mode_plus));
+                    });
 
-                    // Configure for "fast mode" (400 KHz)
                     i2c.timingr.write(|w|
                         w.presc()
-                            .bits(presc_reg)
+                            .bits(timing.presc)
                             .scll()
-                            .bits(scll)
+                            .bits(timing.scll)
                             .sclh()
-                            .bits(sclh)
+                            .bits(timing.sclh)
                             .sdadel()
-                            .bits(sdadel)
+                            .bits(timing.sdadel)
                             .scldel()
-                            .bits(scldel)
+                            .bits(timing.scldel)
                     );
 
-                    // Enable the peripheral
-                    i2c.cr1.write(|w| w.pe().set_bit());
+                    // Enable the peripheral, keeping the noise filter
+                    // configuration
+                    i2c.cr1.modify(|_, w| w.pe().set_bit());
+
+                    Ok(I2c {
+                        i2c,
+                        pins: (),
+                        timeout: timeout_polls(DEFAULT_TIMEOUT, clocks.$pclkX().0),
+                        ker_ck: Hertz(i2cclk),
+                        ker_ck_wakeup: $I2CX::kernel_clk_wakeup(&prec),
+                    })
+                }
+
+            }
 
-                    I2c { i2c }
+            impl<PINS> I2c<$I2CX, PINS> {
+                /// Releases the I2C peripheral and its pins
+                pub fn free(self) -> ($I2CX, rec::$Rec, PINS) {
+                    (self.i2c, rec::$Rec { _marker: PhantomData }, self.pins)
                 }
 
-                /// Releases the I2C peripheral
-                pub fn free(self) -> ($I2CX, rec::$Rec) {
-                    (self.i2c, rec::$Rec { _marker: PhantomData })
+                /// Set the timeout for each wait in master mode. If the
+                /// bus does not respond in time, the peripheral is reset
+                /// and [`Error::Timeout`](Error::Timeout) is returned.
+                ///
+                /// The default is 10ms. The actual timeout is at least
+                /// `timeout`, and may be several times longer depending
+                /// on the core and bus clocks.
+                pub fn set_timeout(&mut self, timeout: MilliSeconds, clocks: &CoreClocks) {
+                    self.timeout = timeout_polls(timeout, clocks.$pclkX().0);
                 }
-            }
 
-            impl I2cExt<$I2CX> for $I2CX {
-                type Rec = rec::$Rec;
+                /// Enable interrupts for the given `event`
+                pub fn listen(&mut self, event: Event) {
+                    self.i2c.cr1.modify(|_, w| match event {
+                        Event::Transmit => w.txie().enabled(),
+                        Event::Receive => w.rxie().enabled(),
+                        Event::TransferComplete => w.tcie().enabled(),
+                        Event::Stop => w.stopie().enabled(),
+                        Event::NotAcknowledge => w.nackie().enabled(),
+                        Event::AddressMatch => w.addrie().enabled(),
+                        Event::Errors => w.errie().enabled(),
+                    });
+                }
 
-                /// Create and initialise a new I2C peripheral.
+                /// Disable interrupts for the given `event`
+                pub fn unlisten(&mut self, event: Event) {
+                    self.i2c.cr1.modify(|_, w| match event {
+                        Event::Transmit => w.txie().disabled(),
+                        Event::Receive => w.rxie().disabled(),
+                        Event::TransferComplete => w.tcie().disabled(),
+                        Event::Stop => w.stopie().disabled(),
+                        Event::NotAcknowledge => w.nackie().disabled(),
+                        Event::AddressMatch => w.addrie().disabled(),
+                        Event::Errors => w.errie().disabled(),
+                    });
+                }
+
+                /// Respond to the 7-bit `address` in slave mode.
+                pub fn enable_own_address1(&mut self, address: u8) {
+                    assert!(address < 0x80);
+
+                    // OA1 may only be changed whilst OA1EN is clear
+                    self.i2c.oar1.write(|w| w.oa1en().disabled());
+                    self.i2c.oar1.write(|w| {
+                        w.oa1()
+                            .bits(u16(address) << 1)
+                            .oa1mode()
+                            .bit7()
+                            .oa1en()
+                            .enabled()
+                    });
+                }
+
+                /// Stop responding to own address 1
+                pub fn disable_own_address1(&mut self) {
+                    self.i2c.oar1.write(|w| w.oa1en().disabled());
+                }
+
+                /// Respond to the 7-bit `address` in slave mode, in
+                /// addition to own address 1.
                 ///
-                /// A tuple of pins `(scl, sda)` for this I2C peripheral should
-                /// be passed as `pins`. This function sets each pin to
-                /// open-drain mode.
+                /// The lowest `masked_bits` bits of `address` are
+                /// ignored when matching, so up to 128 addresses can
+                /// be matched. Reserved addresses are never
+                /// acknowledged.
+                pub fn enable_own_address2(&mut self, address: u8, masked_bits: u8) {
+                    assert!(address < 0x80);
+                    assert!(masked_bits <= 7);
+
+                    // OA2 may only be changed whilst OA2EN is clear
+                    self.i2c.oar2.write(|w| w.oa2en().disabled());
+                    self.i2c.oar2.write(|w| {
+                        w.oa2()
+                            .bits(address)
+                            .oa2msk()
+                            .bits(masked_bits)
+                            .oa2en()
+                            .enabled()
+                    });
+                }
+
+                /// Stop responding to own address 2
+                pub fn disable_own_address2(&mut self) {
+                    self.i2c.oar2.write(|w| w.oa2en().disabled());
+                }
+
+                /// Respond to the general call address (0) in slave
+                /// mode.
+                pub fn general_call(&mut self, enable: bool) {
+                    self.i2c.cr1.modify(|_, w| w.gcen().bit(enable));
+                }
+
+                /// Enable or disable clock stretching in slave mode. It
+                /// is enabled by default.
                 ///
-                /// The frequency of the I2C bus clock is specified by `frequency`.
+                /// Without clock stretching, each received byte must be
+                /// read, and each byte to transmit written, within one
+                /// byte time. Otherwise an overrun or underrun occurs.
+                pub fn clock_stretching(&mut self, enable: bool) {
+                    // NOSTRETCH may only be changed whilst PE is clear
+                    self.i2c.cr1.modify(|_, w| w.pe().clear_bit());
+                    self.i2c.cr1.modify(|_, w| w.nostretch().bit(!enable));
+                    self.i2c.cr1.modify(|_, w| w.pe().set_bit());
+                }
+
+                /// Enable or disable wakeup from Stop mode when an own
+                /// address is matched in slave mode.
                 ///
-                /// # Panics
+                /// The I2C kernel clock must be HSI or CSI, which the
+                /// I2C requests whilst in Stop mode. To wakeup the core,
+                /// the [`AddressMatch`](Event::AddressMatch) event must be
+                /// enabled with [`listen`](I2c::listen), and the I2C
+                /// line unmasked in the EXTI.
                 ///
-                /// Panics if the ratio between `frequency` and the i2c_ker_ck
-                /// is out of bounds. The acceptable range is [4, 8192].
+                /// If the I2C kernel clock selected when the I2C was
+                /// created is neither HSI nor CSI, then
+                /// [`Error::WakeupClock`](Error::WakeupClock) is
+                /// returned.
                 ///
-                /// Panics if the `frequency` is too fast. The maximum is 1MHz.
-                fn i2c<PINS, F>(self, pins: PINS, frequency: F,
-                                prec: rec::$Rec,
-                                clocks: &CoreClocks) -> I2c<$I2CX>
-                where
-                    PINS: Pins<$I2CX>,
-                    F: Into<Hertz>
-                {
-                    let _ = pins.set_open_drain();
+                /// The digital filter is not compatible with wakeup. If
+                /// it was enabled in the [`Config`](Config), then
+                /// [`Error::DigitalFilter`](Error::DigitalFilter) is
+                /// returned, since the bus timing was calculated for the
+                /// filter.
+                pub fn wakeup_from_stop(&mut self, enable: bool) -> Result<(), Error> {
+                    if enable {
+                        if !self.ker_ck_wakeup {
+                            return Err(Error::WakeupClock);
+                        }
+
+                        if self.i2c.cr1.read().dnf().bits() != 0 {
+                            return Err(Error::DigitalFilter);
+                        }
+                    }
 
-                    I2c::$i2cX(self, frequency, prec, clocks)
+                    self.i2c.cr1.modify(|_, w| w.wupen().bit(enable));
+
+                    Ok(())
                 }
 
-                /// Create and initialise a new I2C peripheral. No pin types are
-                /// required.
+                /// Check for a slave mode event.
                 ///
-                /// The frequency of the I2C bus clock is specified by `frequency`.
-                ///
-                /// # Panics
-                ///
-                /// Panics if the ratio between `frequency` and the i2c_ker_ck
-                /// is out of bounds. The acceptable range is [4, 8192].
-                ///
-                /// Panics if the `frequency` is too fast. The maximum is 1MHz.
-                fn i2c_unchecked<F>(self, frequency: F,
-                                    prec: rec::$Rec,
-                                    clocks: &CoreClocks) -> I2c<$I2CX>
-                where
-                    F: Into<Hertz>
-                {
-                    I2c::$i2cX(self, frequency, prec, clocks)
+                /// An address match is acknowledged immediately, and
+                /// when the master will read, any stale data is
+                /// discarded. Each [`TransmitRequest`](SlaveEvent::TransmitRequest)
+                /// must be answered with [`slave_write`](I2c::slave_write).
+                pub fn slave_poll(&mut self) -> nb::Result<SlaveEvent, Error> {
+                    let isr = self.i2c.isr.read();
+
+                    if isr.berr().is_error() {
+                        self.i2c.icr.write(|w| w.berrcf().set_bit());
+                        Err(nb::Error::Other(Error::Bus))
+                    } else if isr.arlo().is_lost() {
+                        self.i2c.icr.write(|w| w.arlocf().set_bit());
+                        Err(nb::Error::Other(Error::Arbitration))
+                    } else if isr.ovr().is_overrun() {
+                        self.i2c.icr.write(|w| w.ovrcf().set_bit());
+                        Err(nb::Error::Other(Error::Overrun))
+                    } else if isr.rxne().is_not_empty() {
+                        let byte = self.i2c.rxdr.read().rxdata().bits();
+                        Ok(SlaveEvent::Received(byte))
+                    } else if isr.stopf().is_stop() {
+                        self.i2c.icr.write(|w| w.stopcf().set_bit());
+                        Ok(SlaveEvent::Stop)
+                    } else if isr.addr().is_match_() {
+                        let direction = if isr.dir().is_read() {
+                            // Discard any stale data in TXDR
+                            self.i2c.isr.write(|w| w.txe().set_bit());
+                            Direction::Read
+                        } else {
+                            Direction::Write
+                        };
+                        let address = isr.addcode().bits();
+
+                        // Release SCL
+                        self.i2c.icr.write(|w| w.addrcf().set_bit());
+
+                        Ok(SlaveEvent::AddressMatch { address, direction })
+                    } else if isr.nackf().is_nack() {
+                        // The master does not acknowledge the last
+                        // byte that it reads
+                        self.i2c.icr.write(|w| w.nackcf().set_bit());
+                        flush_txdr!(self.i2c);
+                        Err(nb::Error::WouldBlock)
+                    } else if isr.txis().is_empty() {
+                        Ok(SlaveEvent::TransmitRequest)
+                    } else {
+                        Err(nb::Error::WouldBlock)
+                    }
                 }
-            }
 
-            impl Write for I2c<$I2CX> {
-                type Error = Error;
+                /// Send a byte to the master in response to a
+                /// [`TransmitRequest`](SlaveEvent::TransmitRequest)
+                pub fn slave_write(&mut self, byte: u8) {
+                    self.i2c.txdr.write(|w| w.txdata().bits(byte));
+                }
 
-                fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
-                    // TODO support transfers of more than 255 bytes
-                    assert!(bytes.len() < 256 && bytes.len() > 0);
+                /// Write `bytes` to the slave at `address`, which may be
+                /// a 7-bit or 10-bit address.
+                pub fn write_to(&mut self, address: Address, bytes: &[u8]) -> Result<(), Error> {
+                    assert!(bytes.len() > 0);
 
                     // Wait for any previous address sequence to end
                     // automatically. This could be up to 50% of a bus
                     // cycle (ie. up to 0.5/freq)
-                    while self.i2c.cr2.read().start().bit_is_set() {};
+                    self.wait_start()?;
 
                     // Set START and prepare to send `bytes`. The
                     // START bit can be set even if the bus is BUSY or
                     // I2C is in slave mode.
-                    self.i2c.cr2.write(|w| {
-                        w.start()
-                            .set_bit()
-                            .sadd()
-                            .bits(u16(addr << 1 | 0))
-                            .add10().clear_bit()
-                            .rd_wrn()
-                            .write()
-                            .nbytes()
-                            .bits(bytes.len() as u8)
-                            .autoend()
-                            .automatic()
-                    });
+                    self.start(address, Direction::Write, bytes.len(), true, false, false);
 
-                    for byte in bytes {
-                        // Wait until we are allowed to send data
-                        // (START has been ACKed or last byte when
-                        // through)
-                        busy_wait!(self.i2c, txis, is_empty);
+                    self.write_bytes(bytes)?;
 
-                        // Put byte on the wire
-                        self.i2c.txdr.write(|w| w.txdata().bits(*byte));
-                    }
                     // automatic STOP
-
-                    Ok(())
+                    self.end_transfer()
                 }
-            }
 
-            impl WriteRead for I2c<$I2CX> {
-                type Error = Error;
-
-                fn write_read(
+                /// Write `bytes` to the slave at `address`, then read
+                /// into `buffer` after a repeated START. The address may
+                /// be a 7-bit or 10-bit address.
+                pub fn write_read_from(
                     &mut self,
-                    addr: u8,
+                    address: Address,
                     bytes: &[u8],
                     buffer: &mut [u8],
                 ) -> Result<(), Error> {
-                    // TODO support transfers of more than 255 bytes
-                    assert!(bytes.len() < 256 && bytes.len() > 0);
-                    assert!(buffer.len() < 256 && buffer.len() > 0);
+                    assert!(bytes.len() > 0);
+                    assert!(buffer.len() > 0);
 
                     // Wait for any previous address sequence to end
                     // automatically. This could be up to 50% of a bus
                     // cycle (ie. up to 0.5/freq)
-                    while self.i2c.cr2.read().start().bit_is_set() {};
+                    self.wait_start()?;
 
                     // Set START and prepare to send `bytes`. The
                     // START bit can be set even if the bus is BUSY or
                     // I2C is in slave mode.
+                    self.start(address, Direction::Write, bytes.len(), false, false, false);
+
+                    self.write_bytes(bytes)?;
+
+                    // Wait until the write finishes before beginning to read.
+                    busy_wait!(self.i2c, tc, is_complete, self.timeout);
+
+                    // reSTART and prepare to receive bytes into `buffer`
+                    self.start(address, Direction::Read, buffer.len(), true, true, false);
+
+                    self.read_bytes(buffer)?;
+
+                    // automatic STOP
+                    self.end_transfer()
+                }
+
+                /// Read into `buffer` from the slave at `address`, which
+                /// may be a 7-bit or 10-bit address.
+                pub fn read_from(&mut self, address: Address, buffer: &mut [u8]) -> Result<(), Error> {
+                    assert!(buffer.len() > 0);
+
+                    // Wait for any previous address sequence to end
+                    // automatically. This could be up to 50% of a bus
+                    // cycle (ie. up to 0.5/freq)
+                    self.wait_start()?;
+
+                    // Set START and prepare to receive bytes into
+                    // `buffer`. The START bit can be set even if the bus
+                    // is BUSY or I2C is in slave mode.
+                    self.start(address, Direction::Read, buffer.len(), true, false, false);
+
+                    self.read_bytes(buffer)?;
+
+                    // automatic STOP
+                    self.end_transfer()
+                }
+
+                /// Execute `operations` with the slave at `address` as a
+                /// single transaction.
+                ///
+                /// The transaction begins with a START, and ends with a
+                /// STOP. Adjacent operations in the same direction are
+                /// transferred without a repeated START between them,
+                /// so several buffers can be written as one. Between
+                /// operations in different directions, a repeated START
+                /// and the address are sent.
+                ///
+                /// ```
+                /// i2c.transaction(address, &mut [
+                ///     Operation::Write(&[register]),
+                ///     Operation::Write(&data),
+                ///     Operation::Read(&mut buffer),
+                /// ])?;
+                /// ```
+                pub fn transaction(
+                    &mut self,
+                    address: Address,
+                    operations: &mut [Operation<'_>],
+                ) -> Result<(), Error> {
+                    assert!(operations.len() > 0);
+
+                    self.wait_start()?;
+
+                    let mut previous = None;
+                    let mut i = 0;
+                    while i < operations.len() {
+                        // Adjacent operations in the same direction
+                        // form one transfer
+                        let direction = operations[i].direction();
+                        let end = i + operations[i..]
+                            .iter()
+                            .take_while(|op| op.direction() == direction)
+                            .count();
+                        let len = operations[i..end].iter().map(Operation::len).sum();
+
+                        if previous.is_some() {
+                            // Wait until the previous transfer finishes
+                            busy_wait!(self.i2c, tc, is_complete, self.timeout);
+                        }
+
+                        // START or reSTART, and STOP automatically after
+                        // the last transfer
+                        let after_write = previous == Some(Direction::Write);
+                        self.start(address, direction, len, end == operations.len(), after_write, false);
+
+                        let mut n = 0;
+                        for op in &mut operations[i..end] {
+                            match op {
+                                Operation::Write(bytes) => {
+                                    for byte in bytes.iter() {
+                                        if n > 0 && n % 255 == 0 {
+                                            self.reload(len - n)?;
+                                        }
+                                        busy_wait!(self.i2c, txis, is_empty, self.timeout);
+                                        self.i2c.txdr.write(|w| w.txdata().bits(*byte));
+                                        n += 1;
+                                    }
+                                }
+                                Operation::Read(buffer) => {
+                                    for byte in buffer.iter_mut() {
+                                        if n > 0 && n % 255 == 0 {
+                                            self.reload(len - n)?;
+                                        }
+                                        busy_wait!(self.i2c, rxne, is_not_empty, self.timeout);
+                                        *byte = self.i2c.rxdr.read().rxdata().bits();
+                                        n += 1;
+                                    }
+                                }
+                            }
+                        }
+
+                        previous = Some(direction);
+                        i = end;
+                    }
+
+                    // automatic STOP
+                    self.end_transfer()
+                }
+
+                /// Set START (or repeated START), and prepare to
+                /// transfer the first bytes of a transfer of `len`
+                /// bytes.
+                ///
+                /// If `autoend`, STOP is sent automatically at the end
+                /// of the transfer. `after_write` indicates a read
+                /// following a write to the same slave, in which case
+                /// only the header of a 10-bit address is resent. If
+                /// `pec`, a PEC byte is sent or checked after the `len`
+                /// bytes.
+                fn start(
+                    &mut self,
+                    address: Address,
+                    direction: Direction,
+                    len: usize,
+                    autoend: bool,
+                    after_write: bool,
+                    pec: bool,
+                ) {
+                    let (sadd, add10) = match address {
+                        Address::SevenBit(addr) => {
+                            assert!(addr < 0x80);
+                            (u16(addr) << 1, false)
+                        }
+                        Address::TenBit(addr) => {
+                            assert!(addr < 0x400);
+                            (addr, true)
+                        }
+                    };
+                    let (nbytes, reload) = nbytes_reload(len + pec as usize);
+
+                    if !after_write {
+                        // Clear any flags left by a previous failed
+                        // transfer
+                        self.i2c.icr.write(|w| w.stopcf().set_bit().nackcf().set_bit());
+                        if self.i2c.isr.read().rxne().is_not_empty() {
+                            let _ = self.i2c.rxdr.read().rxdata().bits();
+                        }
+                    }
+
+                    // PECBYTE cannot be set together with RELOAD
+                    assert!(!(pec && reload));
+
                     self.i2c.cr2.write(|w| {
-                        w.start()
-                            .set_bit()
-                            .sadd()
-                            .bits(u16(addr << 1 | 0))
-                            .add10().clear_bit()
+                        w.sadd()
+                            .bits(sadd)
+                            .add10()
+                            .bit(add10)
+                            .head10r()
+                            .bit(after_write)
                             .rd_wrn()
-                            .write()
+                            .bit(direction == Direction::Read)
                             .nbytes()
-                            .bits(bytes.len() as u8)
+                            .bits(nbytes)
+                            .reload()
+                            .bit(reload)
                             .autoend()
-                            .software()
+                            .bit(autoend)
+                            .pecbyte()
+                            .bit(pec)
+                            .start()
+                            .set_bit()
                     });
+                }
 
-                    for byte in bytes {
-                        // Wait until we are allowed to send data
-                        // (START has been ACKed or last byte went through)
-                        busy_wait!(self.i2c, txis, is_empty);
+                /// Enable SMBus `mode`, with hardware packet error
+                /// checking (PEC) if `pec` is set.
+                ///
+                /// In slave mode, the SMBus host address or device
+                /// default address is also acknowledged.
+                pub fn enable_smbus(&mut self, mode: SmbusMode, pec: bool) {
+                    self.i2c.cr1.modify(|_, w| {
+                        w.smbhen()
+                            .bit(mode == SmbusMode::Host)
+                            .smbden()
+                            .bit(mode == SmbusMode::Device)
+                            .pecen()
+                            .bit(pec)
+                    });
+                }
 
-                        // Put byte on the wire
-                        self.i2c.txdr.write(|w| w.txdata().bits(*byte));
+                /// Disable SMBus mode and PEC
+                pub fn disable_smbus(&mut self) {
+                    self.i2c.cr1.modify(|_, w| {
+                        w.smbhen()
+                            .clear_bit()
+                            .smbden()
+                            .clear_bit()
+                            .pecen()
+                            .clear_bit()
+                            .alerten()
+                            .clear_bit()
+                    });
+                }
+
+                /// Enable or disable SMBus alerts.
+                ///
+                /// In host mode, this enables reception of alerts on
+                /// the SMBA pin, see [`is_alert`](I2c::is_alert). In
+                /// device mode, this drives the SMBA pin low to signal
+                /// an alert to the host.
+                pub fn smbus_alert(&mut self, enable: bool) {
+                    self.i2c.cr1.modify(|_, w| w.alerten().bit(enable));
+                }
+
+                /// Return `true` if an SMBus alert has been received in
+                /// host mode
+                pub fn is_alert(&self) -> bool {
+                    self.i2c.isr.read().alert().is_alert()
+                }
+
+                /// Clear the SMBus alert flag
+                pub fn clear_alert(&mut self) {
+                    self.i2c.icr.write(|w| w.alertcf().set_bit());
+                }
+
+                /// Enable SMBus timeout detection.
+                ///
+                /// A timeout is reported as
+                /// [`Error::Timeout`](Error::Timeout) if SCL is held low
+                /// for longer than `scl_low` (tTIMEOUT, 25ms for SMBus).
+                /// If `clock_extension` is specified, a timeout is also
+                /// reported when the cumulative clock low extension
+                /// exceeds it (tLOW:MEXT = 10ms for a host, or tLOW:SEXT
+                /// = 25ms for a device).
+                ///
+                /// Returns [`Error::TimeoutRange`](Error::TimeoutRange)
+                /// if a timeout is zero, or too long for the I2C kernel
+                /// clock. The timeouts are then left unchanged.
+                pub fn smbus_timeout(
+                    &mut self,
+                    scl_low: MilliSeconds,
+                    clock_extension: Option<MilliSeconds>,
+                ) -> Result<(), Error> {
+                    let i2cclk = self.ker_ck.0;
+                    let timeouta = timeout_ticks(scl_low, i2cclk)
+                        .ok_or(Error::TimeoutRange)?;
+                    let timeoutb = clock_extension
+                        .map(|t| timeout_ticks(t, i2cclk).ok_or(Error::TimeoutRange))
+                        .transpose()?;
+
+                    // The timeouts may only be changed whilst disabled
+                    self.i2c.timeoutr.write(|w| w.timouten().clear_bit().texten().clear_bit());
+                    self.i2c.timeoutr.write(|w| {
+                        w.timeouta()
+                            .bits(timeouta)
+                            .tidle()
+                            .disabled()
+                            .timouten()
+                            .set_bit()
+                            .timeoutb()
+                            .bits(timeoutb.unwrap_or(0))
+                            .texten()
+                            .bit(timeoutb.is_some())
+                    });
+
+                    Ok(())
+                }
+
+                /// Disable SMBus timeout detection
+                pub fn disable_smbus_timeout(&mut self) {
+                    self.i2c.timeoutr.write(|w| w.timouten().clear_bit().texten().clear_bit());
+                }
+
+                /// SMBus Send Byte
+                pub fn smbus_send_byte(&mut self, addr: u8, byte: u8) -> Result<(), Error> {
+                    self.smbus_write(addr, &[byte], &[])
+                }
+
+                /// SMBus Receive Byte
+                pub fn smbus_receive_byte(&mut self, addr: u8) -> Result<u8, Error> {
+                    let mut buffer = [0];
+                    self.smbus_read(addr, &[], &mut buffer)?;
+                    Ok(buffer[0])
+                }
+
+                /// SMBus Write Byte
+                pub fn smbus_write_byte(&mut self, addr: u8, command: u8, byte: u8) -> Result<(), Error> {
+                    self.smbus_write(addr, &[command, byte], &[])
+                }
+
+                /// SMBus Write Word. The word is sent LSB first
+                pub fn smbus_write_word(&mut self, addr: u8, command: u8, word: u16) -> Result<(), Error> {
+                    self.smbus_write(addr, &[command, word as u8, (word >> 8) as u8], &[])
+                }
+
+                /// SMBus Read Byte
+                pub fn smbus_read_byte(&mut self, addr: u8, command: u8) -> Result<u8, Error> {
+                    let mut buffer = [0];
+                    self.smbus_read(addr, &[command], &mut buffer)?;
+                    Ok(buffer[0])
+                }
+
+                /// SMBus Read Word. The word is received LSB first
+                pub fn smbus_read_word(&mut self, addr: u8, command: u8) -> Result<u16, Error> {
+                    let mut buffer = [0; 2];
+                    self.smbus_read(addr, &[command], &mut buffer)?;
+                    Ok(u16(buffer[0]) | u16(buffer[1]) << 8)
+                }
+
+                /// SMBus Process Call. Sends `word` and returns the word
+                /// received
+                pub fn smbus_process_call(&mut self, addr: u8, command: u8, word: u16) -> Result<u16, Error> {
+                    let mut buffer = [0; 2];
+                    self.smbus_read(addr, &[command, word as u8, (word >> 8) as u8], &mut buffer)?;
+                    Ok(u16(buffer[0]) | u16(buffer[1]) << 8)
+                }
+
+                /// SMBus Block Write. The byte count is sent before
+                /// `bytes`
+                ///
+                /// Returns [`Error::BlockSize`](Error::BlockSize) if
+                /// `bytes` is empty, or longer than 253 bytes, or 252
+                /// bytes when PEC is enabled.
+                pub fn smbus_block_write(&mut self, addr: u8, command: u8, bytes: &[u8]) -> Result<(), Error> {
+                    let pec = self.i2c.cr1.read().pecen().bit_is_set();
+
+                    // The command and byte count are sent in the same
+                    // NBYTES transfer as the block. SMBus requires a
+                    // byte count of at least 1
+                    if bytes.is_empty() || smbus_block_nbytes(2 + bytes.len(), pec).is_none() {
+                        return Err(Error::BlockSize);
                     }
 
-                    // Wait until the write finishes before beginning to read.
-                    busy_wait!(self.i2c, tc, is_complete);
+                    self.smbus_write(addr, &[command, bytes.len() as u8], bytes)
+                }
 
-                    // reSTART and prepare to receive bytes into `buffer`
+                /// SMBus Block Read. Returns the number of bytes read
+                /// into `buffer`, as indicated by the byte count sent by
+                /// the slave.
+                ///
+                /// If the byte count is zero, larger than the length of
+                /// `buffer` or 255 when PEC is enabled, the peripheral is
+                /// reset and [`Error::BlockSize`](Error::BlockSize) is
+                /// returned.
+                pub fn smbus_block_read(&mut self, addr: u8, command: u8, buffer: &mut [u8]) -> Result<usize, Error> {
+                    let pec = self.i2c.cr1.read().pecen().bit_is_set();
+
+                    self.wait_start()?;
+
+                    self.start(Address::SevenBit(addr), Direction::Write, 1, false, false, false);
+                    self.write_bytes(&[command])?;
+                    busy_wait!(self.i2c, tc, is_complete, self.timeout);
+
+                    // reSTART and receive the byte count, then reload
+                    // NBYTES with the count
                     self.i2c.cr2.write(|w| {
                         w.sadd()
-                            .bits(u16(addr << 1 | 1))
-                            .add10().clear_bit()
+                            .bits(u16(addr) << 1)
                             .rd_wrn()
                             .read()
                             .nbytes()
-                            .bits(buffer.len() as u8)
-                            .start()
-                            .set_bit()
+                            .bits(1)
+                            .reload()
+                            .bit(true)
                             .autoend()
                             .automatic()
+                            .start()
+                            .set_bit()
+                    });
+                    busy_wait!(self.i2c, rxne, is_not_empty, self.timeout);
+                    let count = self.i2c.rxdr.read().rxdata().bits() as usize;
+
+                    busy_wait!(self.i2c, tcr, is_complete, self.timeout);
+                    let nbytes = match smbus_block_nbytes(count, pec) {
+                        Some(nbytes) if count > 0 && count <= buffer.len() => nbytes,
+                        _ => {
+                            // Release the bus
+                            software_reset!(self.i2c);
+                            return Err(Error::BlockSize);
+                        }
+                    };
+                    self.i2c.cr2.modify(|_, w| {
+                        w.nbytes()
+                            .bits(nbytes)
+                            .reload()
+                            .bit(false)
+                            .pecbyte()
+                            .bit(pec)
                     });
 
-                    for byte in buffer {
+                    self.read_bytes(&mut buffer[..count])?;
+                    self.smbus_end(pec)?;
+
+                    Ok(count)
+                }
+
+                /// Write the `header` bytes followed by `bytes` in a
+                /// single SMBus message
+                fn smbus_write(&mut self, addr: u8, header: &[u8], bytes: &[u8]) -> Result<(), Error> {
+                    let pec = self.i2c.cr1.read().pecen().bit_is_set();
+
+                    self.wait_start()?;
+
+                    let len = header.len() + bytes.len();
+                    self.start(Address::SevenBit(addr), Direction::Write, len, true, false, pec);
+
+                    self.write_bytes(header)?;
+                    self.write_bytes(bytes)?;
+                    self.end_transfer()
+                }
+
+                /// Write `bytes` (if any), then read into `buffer` in a
+                /// single SMBus message
+                fn smbus_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+                    let pec = self.i2c.cr1.read().pecen().bit_is_set();
+
+                    self.wait_start()?;
+
+                    let after_write = !bytes.is_empty();
+                    if after_write {
+                        self.start(Address::SevenBit(addr), Direction::Write, bytes.len(), false, false, false);
+                        self.write_bytes(bytes)?;
+                        busy_wait!(self.i2c, tc, is_complete, self.timeout);
+                    }
+
+                    self.start(Address::SevenBit(addr), Direction::Read, buffer.len(), true, after_write, pec);
+                    self.read_bytes(buffer)?;
+                    self.smbus_end(pec)
+                }
+
+                /// Receive the PEC byte if `pec` is set, then wait for
+                /// the automatic STOP. The PEC byte is checked by the
+                /// hardware.
+                fn smbus_end(&mut self, pec: bool) -> Result<(), Error> {
+                    if pec {
+                        busy_wait!(self.i2c, rxne, is_not_empty, self.timeout);
+                        let _ = self.i2c.rxdr.read().rxdata().bits();
+                    }
+
+                    self.end_transfer()
+                }
+
+                /// Wait for any previous address sequence to end
+                fn wait_start(&mut self) -> Result<(), Error> {
+                    let mut polls = self.timeout;
+
+                    while self.i2c.cr2.read().start().bit_is_set() {
+                        if polls == 0 {
+                            software_reset!(self.i2c);
+                            return Err(Error::Timeout);
+                        }
+                        polls -= 1;
+                    }
+
+                    Ok(())
+                }
+
+                /// Wait for the automatic STOP at the end of a transfer,
+                /// so that a NACK or error on the last byte is reported
+                fn end_transfer(&mut self) -> Result<(), Error> {
+                    busy_wait!(self.i2c, stopf, is_stop, self.timeout);
+                    self.i2c.icr.write(|w| w.stopcf().set_bit());
+
+                    Ok(())
+                }
+
+                /// Wait until the current 255 bytes have been
+                /// transferred, then reload NBYTES for the `remaining`
+                /// bytes
+                fn reload(&mut self, remaining: usize) -> Result<(), Error> {
+                    busy_wait!(self.i2c, tcr, is_complete, self.timeout);
+
+                    let (nbytes, reload) = nbytes_reload(remaining);
+                    self.i2c.cr2.modify(|_, w| {
+                        w.nbytes().bits(nbytes).reload().bit(reload)
+                    });
+
+                    Ok(())
+                }
+
+                /// Send `bytes` in a transfer that has already been
+                /// started
+                fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+                    for (i, byte) in bytes.iter().enumerate() {
+                        if i > 0 && i % 255 == 0 {
+                            self.reload(bytes.len() - i)?;
+                        }
+
+                        // Wait until we are allowed to send data
+                        // (START has been ACKed or last byte when
+                        // through)
+                        busy_wait!(self.i2c, txis, is_empty, self.timeout);
+
+                        // Put byte on the wire
+                        self.i2c.txdr.write(|w| w.txdata().bits(*byte));
+                    }
+
+                    Ok(())
+                }
+
+                /// Receive bytes into `buffer` in a transfer that has
+                /// already been started
+                fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+                    let len = buffer.len();
+
+                    for (i, byte) in buffer.iter_mut().enumerate() {
+                        if i > 0 && i % 255 == 0 {
+                            self.reload(len - i)?;
+                        }
+
                         // Wait until we have received something
-                        busy_wait!(self.i2c, rxne, is_not_empty);
+                        busy_wait!(self.i2c, rxne, is_not_empty, self.timeout);
 
                         *byte = self.i2c.rxdr.read().rxdata().bits();
                     }
 
-                    // automatic STOP
+                    Ok(())
+                }
+            }
+
+            impl<PINS> I2c<$I2CX, PINS> {
+                /// Convert into an I2C master driven by interrupts. The
+                /// event and error interrupts are enabled.
+                pub fn interrupt_driven(mut self) -> InterruptI2c<$I2CX, PINS> {
+                    for &event in &[
+                        Event::Transmit,
+                        Event::Receive,
+                        Event::TransferComplete,
+                        Event::Stop,
+                        Event::NotAcknowledge,
+                        Event::Errors,
+                    ] {
+                        self.listen(event);
+                    }
+
+                    InterruptI2c {
+                        i2c: self,
+                        transaction: None,
+                        direction: Direction::Write,
+                        index: 0,
+                        error: None,
+                        result: None,
+                    }
+                }
+            }
+
+            impl<PINS> InterruptI2c<$I2CX, PINS> {
+                /// Start `transaction`. Transactions are not queued, so
+                /// [`Busy`] is returned with the transaction if another
+                /// transaction is in progress, or has not been
+                /// collected with [`take`](InterruptI2c::take).
+                ///
+                /// # Panics
+                ///
+                /// Panics if the transaction has no bytes to write or
+                /// read.
+                pub fn submit(&mut self, transaction: Transaction) -> Result<(), Busy> {
+                    if self.transaction.is_some() {
+                        return Err(Busy(transaction));
+                    }
+
+                    let (address, direction, len, autoend) = match &transaction {
+                        Transaction::Write { address, bytes } => {
+                            (*address, Direction::Write, bytes.len(), true)
+                        }
+                        Transaction::Read { address, buffer } => {
+                            (*address, Direction::Read, buffer.len(), true)
+                        }
+                        Transaction::WriteRead { address, bytes, buffer } => {
+                            assert!(buffer.len() > 0);
+                            (*address, Direction::Write, bytes.len(), false)
+                        }
+                    };
+                    assert!(len > 0);
+
+                    self.transaction = Some(transaction);
+                    self.direction = direction;
+                    self.index = 0;
+                    self.error = None;
+                    self.result = None;
+
+                    // Set START. The rest of the transaction is
+                    // processed by `on_interrupt`
+                    self.i2c.start(address, direction, len, autoend, false, false);
 
                     Ok(())
                 }
+
+                /// Returns `true` if a transaction is in progress
+                pub fn is_busy(&self) -> bool {
+                    self.transaction.is_some() && self.result.is_none()
+                }
+
+                /// Take the completed transaction and its result,
+                /// returning the buffers. Returns `None` if no
+                /// transaction has completed.
+                pub fn take(&mut self) -> Option<(Transaction, Result<(), Error>)> {
+                    let result = self.result.take()?;
+                    self.transaction.take().map(|t| (t, result))
+                }
+
+                /// Process the transaction. This must be called from both
+                /// the I2C event and error interrupts.
+                ///
+                /// Returns the result when the transaction completes.
+                pub fn on_interrupt(&mut self) -> Option<Result<(), Error>> {
+                    let i2c = &self.i2c.i2c;
+                    let isr = i2c.isr.read();
+
+                    if self.transaction.is_none() || self.result.is_some() {
+                        // Nothing to do. Clear any remaining flags
+                        i2c.icr.write(|w| {
+                            w.stopcf()
+                                .set_bit()
+                                .nackcf()
+                                .set_bit()
+                                .berrcf()
+                                .set_bit()
+                                .arlocf()
+                                .set_bit()
+                        });
+                        if isr.rxne().is_not_empty() {
+                            let _ = i2c.rxdr.read().rxdata().bits();
+                        }
+                        flush_txdr!(i2c);
+                        return None;
+                    }
+
+                    if isr.berr().is_error() {
+                        i2c.icr.write(|w| w.berrcf().set_bit());
+                        flush_txdr!(i2c);
+                        return self.complete(Err(Error::Bus));
+                    }
+                    if isr.arlo().is_lost() {
+                        i2c.icr.write(|w| w.arlocf().set_bit());
+                        flush_txdr!(i2c);
+                        return self.complete(Err(Error::Arbitration));
+                    }
+                    if isr.nackf().bit_is_set() {
+                        // A STOP is sent automatically, which
+                        // completes the transaction
+                        i2c.icr.write(|w| w.nackcf().set_bit());
+                        flush_txdr!(i2c);
+                        self.error = Some(Error::NotAcknowledge);
+                    }
+
+                    if isr.txis().is_empty() && self.error.is_none() {
+                        let byte = match &self.transaction {
+                            Some(Transaction::Write { bytes, .. })
+                            | Some(Transaction::WriteRead { bytes, .. }) => {
+                                bytes.get(self.index).copied().unwrap_or(0)
+                            }
+                            _ => 0,
+                        };
+                        i2c.txdr.write(|w| w.txdata().bits(byte));
+                        self.index += 1;
+                    }
+
+                    if isr.rxne().is_not_empty() {
+                        let byte = i2c.rxdr.read().rxdata().bits();
+                        match &mut self.transaction {
+                            Some(Transaction::Read { buffer, .. })
+                            | Some(Transaction::WriteRead { buffer, .. }) => {
+                                if let Some(b) = buffer.get_mut(self.index) {
+                                    *b = byte;
+                                }
+                            }
+                            _ => {}
+                        }
+                        self.index += 1;
+                    }
+
+                    if isr.tcr().is_complete() {
+                        // Next 255 bytes
+                        let len = match &self.transaction {
+                            Some(Transaction::Write { bytes, .. }) => bytes.len(),
+                            Some(Transaction::WriteRead { bytes, .. })
+                                if self.direction == Direction::Write => bytes.len(),
+                            Some(Transaction::Read { buffer, .. })
+                            | Some(Transaction::WriteRead { buffer, .. }) => buffer.len(),
+                            None => 0,
+                        };
+                        let (nbytes, reload) = nbytes_reload(len - self.index);
+                        i2c.cr2.modify(|_, w| {
+                            w.nbytes().bits(nbytes).reload().bit(reload)
+                        });
+                    } else if isr.tc().is_complete() {
+                        // The write of a write-read has completed.
+                        // reSTART and receive bytes into `buffer`
+                        if let Some(Transaction::WriteRead { address, buffer, .. }) = &self.transaction {
+                            let (address, len) = (*address, buffer.len());
+
+                            self.direction = Direction::Read;
+                            self.index = 0;
+                            self.i2c.start(address, Direction::Read, len, true, true, false);
+                        }
+                    }
+
+                    if isr.stopf().is_stop() {
+                        self.i2c.i2c.icr.write(|w| w.stopcf().set_bit());
+
+                        let result = match self.error.take() {
+                            Some(error) => Err(error),
+                            None => Ok(()),
+                        };
+                        return self.complete(result);
+                    }
+
+                    None
+                }
+
+                /// Record the result of the transaction
+                fn complete(&mut self, result: Result<(), Error>) -> Option<Result<(), Error>> {
+                    self.result = Some(result);
+                    Some(result)
+                }
+
+                /// Releases the I2C, disabling its interrupts. Any
+                /// transaction in progress is abandoned.
+                pub fn free(self) -> I2c<$I2CX, PINS> {
+                    let mut i2c = self.i2c;
+
+                    for &event in &[
+                        Event::Transmit,
+                        Event::Receive,
+                        Event::TransferComplete,
+                        Event::Stop,
+                        Event::NotAcknowledge,
+                        Event::Errors,
+                    ] {
+                        i2c.unlisten(event);
+                    }
+
+                    i2c
+                }
+            }
+
+            impl I2cExt<$I2CX> for $I2CX {
+                type Rec = rec::$Rec;
+
+                /// Create and initialise a new I2C peripheral.
+                ///
+                /// A tuple of pins `(scl, sda)` for this I2C peripheral should
+                /// be passed as `pins`. This function sets each pin to
+                /// open-drain mode. The pins are returned by `free`.
+                ///
+                /// The bus frequency and timing is specified by
+                /// `config`, which may simply be a frequency. Above
+                /// 400kHz, Fast-mode Plus drive is enabled in `syscfg`.
+                ///
+                /// Returns a [`TimingError`] if the bus timing cannot be
+                /// met with the I2C kernel clock.
+                fn i2c<PINS, C>(self, pins: PINS, config: C,
+                                prec: rec::$Rec,
+                                clocks: &CoreClocks,
+                                syscfg: &mut SYSCFG) -> Result<I2c<$I2CX, PINS>, TimingError>
+                where
+                    PINS: Pins<$I2CX>,
+                    C: Into<Config>
+                {
+                    let pins = pins.set_open_drain();
+
+                    Ok(I2c::$i2cX(self, config, prec, clocks, syscfg)?.with_pins(pins))
+                }
+
+                /// Create and initialise a new I2C peripheral. No pin types are
+                /// required.
+                ///
+                /// The bus frequency and timing is specified by
+                /// `config`, which may simply be a frequency. Above
+                /// 400kHz, Fast-mode Plus drive is enabled in `syscfg`.
+                ///
+                /// Returns a [`TimingError`] if the bus timing cannot be
+                /// met with the I2C kernel clock.
+                fn i2c_unchecked<C>(self, config: C,
+                                    prec: rec::$Rec,
+                                    clocks: &CoreClocks,
+                                    syscfg: &mut SYSCFG) -> Result<I2c<$I2CX>, TimingError>
+                where
+                    C: Into<Config>
+                {
+                    I2c::$i2cX(self, config, prec, clocks, syscfg)
+                }
             }
 
-            impl Read for I2c<$I2CX> {
-            type Error = Error;
-
-            fn read(
-                &mut self,
-                addr: u8,
-                buffer: &mut [u8],
-            ) -> Result<(), Error> {
-                // TODO support transfers of more than 255 bytes
-                assert!(buffer.len() < 256 && buffer.len() > 0);
-
-                // Wait for any previous address sequence to end
-                // automatically. This could be up to 50% of a bus
-                // cycle (ie. up to 0.5/freq)
-                while self.i2c.cr2.read().start().bit_is_set() {};
-
-                // Set START and prepare to receive bytes into
-                // `buffer`. The START bit can be set even if the bus
-                // is BUSY or I2C is in slave mode.
-                self.i2c.cr2.write(|w| {
-                    w.sadd()
-                        .bits((addr << 1 | 0) as u16)
-                        .rd_wrn()
-                        .read()
-                        .nbytes()
-                        .bits(buffer.len() as u8)
-                        .start()
-                        .set_bit()
-                        .autoend()
-                        .automatic()
-                });
-
-                for byte in buffer {
-                    // Wait until we have received something
-                    busy_wait!(self.i2c, rxne, is_not_empty);
-
-                    *byte = self.i2c.rxdr.read().rxdata().bits();
-                }
-
-                // automatic STOP
-
-                Ok(())
+            impl<PINS> Write for I2c<$I2CX, PINS> {
+                type Error = Error;
+
+                fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
+                    self.write_to(Address::SevenBit(addr), bytes)
+                }
             }
+
+            impl<PINS> WriteRead for I2c<$I2CX, PINS> {
+                type Error = Error;
+
+                fn write_read(
+                    &mut self,
+                    addr: u8,
+                    bytes: &[u8],
+                    buffer: &mut [u8],
+                ) -> Result<(), Error> {
+                    self.write_read_from(Address::SevenBit(addr), bytes, buffer)
+                }
+            }
+
+            impl<PINS> Read for I2c<$I2CX, PINS> {
+                type Error = Error;
+
+                fn read(
+                    &mut self,
+                    addr: u8,
+                    buffer: &mut [u8],
+                ) -> Result<(), Error> {
+                    self.read_from(Address::SevenBit(addr), buffer)
+                }
             }
         )+
     };
@@ -565,8 +2129,165 @@ pins! {
 }
 
 i2c!(
-    I2C1: (i2c1, I2c1, pclk1),
-    I2C2: (i2c2, I2c2, pclk1),
-    I2C3: (i2c3, I2c3, pclk1),
-    I2C4: (i2c4, I2c4, pclk4),
+    I2C1: (i2c1, I2c1, pclk1, i2c1fmp),
+    I2C2: (i2c2, I2c2, pclk1, i2c2fmp),
+    I2C3: (i2c3, I2c3, pclk1, i2c3fmp),
+    I2C4: (i2c4, I2c4, pclk4, i2c4fmp),
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Calculate the timing for `config`, and check it against the I2C
+    /// specification
+    fn check(config: Config, i2cclk: u32) -> Timing {
+        let timing = config.timing(Hertz(i2cclk)).unwrap();
+        println!("{} Hz: {:?}", config.frequency, timing);
+
+        let spec = match config.frequency {
+            1..=100_000 => &STANDARD_MODE,
+            100_001..=400_000 => &FAST_MODE,
+            _ => &FAST_MODE_PLUS,
+        };
+
+        // Times in nanoseconds
+        let t_i2cclk = 1e9 / i2cclk as f64;
+        let t_presc = (timing.presc as f64 + 1.) * t_i2cclk;
+        let t_r = config.rise_time as f64;
+        let t_f = config.fall_time as f64;
+        let dnf = config.digital_filter as f64;
+        let (t_af_min, t_af_max) = if config.analog_filter {
+            (50., 260.)
+        } else {
+            (0., 0.)
+        };
+        let t_sync = t_af_min + (dnf + 2.) * t_i2cclk;
+
+        // SCL
+        let t_low = (timing.scll as f64 + 1.) * t_presc + t_sync;
+        let t_high = (timing.sclh as f64 + 1.) * t_presc + t_sync;
+        assert!(t_low >= spec.t_low_min as f64);
+        assert!(t_high >= spec.t_high_min as f64);
+
+        let freq = 1e9 / (t_low + t_high + t_r + t_f);
+        println!("==> {} Hz", freq);
+        assert!(freq <= config.frequency as f64);
+        assert!(freq > 0.9 * config.frequency as f64);
+
+        // SDA
+        let sdadel = timing.sdadel as f64 * t_presc;
+        let t_hd_dat = sdadel + t_af_min + (dnf + 3.) * t_i2cclk - t_f;
+        let t_vd_dat = sdadel + t_af_max + (dnf + 4.) * t_i2cclk + t_r;
+        let t_su_dat = (timing.scldel as f64 + 1.) * t_presc - t_r;
+        assert!(t_hd_dat >= 0.);
+        assert!(t_vd_dat <= spec.t_vd_dat_max as f64);
+        assert!(t_su_dat >= spec.t_su_dat_min as f64);
+
+        assert_eq!(timing.fast_mode_plus, config.frequency > 400_000);
+
+        timing
+    }
+
+    #[test]
+    /// Test standard-mode timing
+    fn timing_standard_mode() {
+        for &i2cclk in &[8_000_000, 64_000_000, 100_000_000, 120_000_000] {
+            check(Config::new(Hertz(100_000)), i2cclk);
+            check(Config::new(Hertz(50_000)), i2cclk);
+        }
+        check(
+            Config::new(Hertz(100_000)).rise_time(1000).fall_time(300),
+            100_000_000,
+        );
+    }
+
+    #[test]
+    /// Test fast-mode timing
+    fn timing_fast_mode() {
+        for &i2cclk in &[16_000_000, 64_000_000, 100_000_000, 120_000_000] {
+            check(Config::new(Hertz(400_000)), i2cclk);
+            check(Config::new(Hertz(200_000)), i2cclk);
+        }
+        check(
+            Config::new(Hertz(400_000)).rise_time(300).fall_time(30),
+            100_000_000,
+        );
+    }
+
+    #[test]
+    /// Test fast-mode plus timing
+    fn timing_fast_mode_plus() {
+        for &i2cclk in &[64_000_000, 100_000_000, 120_000_000] {
+            check(Config::new(Hertz(1_000_000)), i2cclk);
+        }
+        check(
+            Config::new(Hertz(1_000_000)).analog_filter(false),
+            32_000_000,
+        );
+        check(
+            Config::new(Hertz(1_000_000)).rise_time(120).fall_time(50),
+            100_000_000,
+        );
+    }
+
+    #[test]
+    /// Test timing with noise filters
+    fn timing_filters() {
+        let config = Config::new(Hertz(400_000));
+
+        check(config.analog_filter(false), 100_000_000);
+        check(config.digital_filter(4), 100_000_000);
+        check(config.analog_filter(false).digital_filter(15), 100_000_000);
+    }
+
+    #[test]
+    /// Test configurations that cannot be met
+    fn timing_errors() {
+        let timing = |config: Config, i2cclk| config.timing(Hertz(i2cclk));
+
+        assert_eq!(
+            timing(Config::new(Hertz(0)), 100_000_000),
+            Err(TimingError::InvalidFrequency)
+        );
+        assert_eq!(
+            timing(Config::new(Hertz(1_100_000)), 100_000_000),
+            Err(TimingError::InvalidFrequency)
+        );
+        assert_eq!(
+            timing(Config::new(Hertz(400_000)).rise_time(500), 100_000_000),
+            Err(TimingError::RiseFallTime)
+        );
+        assert_eq!(
+            timing(Config::new(Hertz(1_000_000)), 4_000_000),
+            Err(TimingError::ClockTooSlow)
+        );
+        assert_eq!(
+            timing(Config::new(Hertz(10_000)), 120_000_000),
+            Err(TimingError::ClockTooFast)
+        );
+    }
+
+    #[test]
+    /// Test SMBus block byte counts
+    fn smbus_block_counts() {
+        assert_eq!(smbus_block_nbytes(32, false), Some(32));
+        assert_eq!(smbus_block_nbytes(32, true), Some(33));
+        assert_eq!(smbus_block_nbytes(255, false), Some(255));
+        assert_eq!(smbus_block_nbytes(255, true), None);
+        assert_eq!(smbus_block_nbytes(2 + 253, false), Some(255));
+        assert_eq!(smbus_block_nbytes(2 + 253, true), None);
+        assert_eq!(smbus_block_nbytes(2 + 252, true), Some(255));
+        assert_eq!(smbus_block_nbytes(0, false), None);
+    }
+
+    #[test]
+    /// Test SMBus TIMEOUTA and TIMEOUTB values
+    fn smbus_timeout_ticks() {
+        assert_eq!(timeout_ticks(MilliSeconds(25), 64_000_000), Some(781));
+        assert_eq!(timeout_ticks(MilliSeconds(131), 64_000_000), Some(4093));
+        assert_eq!(timeout_ticks(MilliSeconds(132), 64_000_000), None);
+        assert_eq!(timeout_ticks(MilliSeconds(0), 64_000_000), None);
+        assert_eq!(timeout_ticks(MilliSeconds(u32::MAX), 400_000_000), None);
+    }
+}
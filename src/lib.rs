@@ -22,6 +22,7 @@
 //! * [Inter Integrated Circuit (I2C)](crate::i2c)
 //! * [Serial Peripheral Interface (SPI)](crate::spi)
 //! * [Serial Audio Interface](crate::sai)
+//! * [Shared SPI and I2C Busses](crate::bus)
 //!
 //! Timing functions
 //!
@@ -110,6 +111,8 @@ pub use crate::stm32::interrupt;
 #[cfg(feature = "device-selected")]
 pub mod adc;
 #[cfg(feature = "device-selected")]
+pub mod bus;
+#[cfg(feature = "device-selected")]
 pub mod dac;
 #[cfg(feature = "device-selected")]
 pub mod delay;
@@ -14,7 +14,7 @@
 //! let dp = ...;                   // Device peripherals
 //! let (sck, miso, mosi) = ...;    // GPIO pins
 //!
-//! let spi = dp.SPI1.spi((sck, miso, mosi), spi::MODE_0, 1.mhz(), ccdr.peripheral.SPI1, &ccdr.clocks);
+//! let spi = dp.SPI1.spi((sck, miso, mosi), spi::MODE_0, 1.mhz(), ccdr.peripheral.SPI1, &ccdr.clocks)?;
 //! ```
 //!
 //! The GPIO pins should be supplied as a
@@ -28,7 +28,68 @@
 //! filler types instead:
 //!
 //! ```
-//! let spi = dp.SPI1.spi((sck, spi::NoMiso, mosi), spi::MODE_0, 1.mhz(), ccdr.peripheral.SPI1, &ccdr.clocks);
+//! let spi = dp.SPI1.spi((sck, spi::NoMiso, mosi), spi::MODE_0, 1.mhz(), ccdr.peripheral.SPI1, &ccdr.clocks)?;
+//! ```
+//!
+//! ## Blocking Transactions
+//!
+//! The blocking `Transfer` and `Write` implementations program the
+//! number of words into the peripheral, and keep its FIFO filled for
+//! the duration of the transaction. Words are moved through the FIFO
+//! in packed 32-bit accesses where possible. If CS is managed by the
+//! peripheral ([`Config::manage_cs`]), it is held asserted for the
+//! whole transaction.
+//!
+//! A transaction is limited to 65535 words. Longer slices are split
+//! into consecutive transactions, each with its own CRC if enabled. A
+//! hardware managed CS is released between them.
+//!
+//! ## Half-duplex and Simplex
+//!
+//! Devices with a single bidirectional data line (3-wire SPI) can be
+//! used by selecting [`CommunicationMode::HalfDuplex`]. In master mode
+//! the data line is the MOSI pin, so MISO can be omitted. Simplex
+//! modes are also supported. Operations that are not possible in the
+//! selected mode, such as a `transfer` on a half-duplex bus, return
+//! [`Error::InvalidMode`]:
+//!
+//! ```
+//! let config = spi::Config::new(spi::MODE_0)
+//!     .communication_mode(spi::CommunicationMode::HalfDuplex);
+//! let mut spi: spi::Spi<_, u8> = dp.SPI1.spi((sck, spi::NoMiso, mosi), config, 1.mhz(), ccdr.peripheral.SPI1, &ccdr.clocks)?;
+//!
+//! spi.write(&[0x80])?;              // Transmit on the data line
+//! let mut buffer = [0; 2];
+//! spi.receive(&mut buffer)?;        // Then switch direction and receive
+//! ```
+//!
+//! ## CRC
+//!
+//! Hardware CRC calculation can be enabled with [`Config::crc`]. The
+//! CRC is then appended to each blocking transfer or write, and the
+//! received CRC is checked. A mismatch is reported as
+//! [`Error::Crc`](Error::Crc). The CRC length must be a multiple of
+//! the word size, otherwise the constructor returns
+//! [`Error::InvalidMode`].
+//!
+//! ```
+//! // CRC-16-CCITT, x^16 + x^12 + x^5 + 1
+//! let config = spi::Config::new(spi::MODE_0).crc(0x1021, 16);
+//! let mut spi: spi::Spi<_, u8> = dp.SPI1.spi((sck, miso, mosi), config, 1.mhz(), ccdr.peripheral.SPI1, &ccdr.clocks)?;
+//! ```
+//!
+//! ## Frame Format
+//!
+//! The Motorola frame format is used by default. The TI synchronous
+//! serial frame format can be selected with [`Config::frame_format`],
+//! in which case the clock polarity and phase are fixed by the
+//! hardware and the SPI mode is ignored. Data is sent MSB first unless
+//! [`Config::lsb_first`] is set.
+//!
+//! ```
+//! let config = spi::Config::new(spi::MODE_0)
+//!     .frame_format(spi::FrameFormat::Ti)
+//!     .inter_word_delay(2);
 //! ```
 //!
 //! ## Word Sizes
@@ -40,7 +101,7 @@
 //!
 //! For example, an explict type annotation:
 //! ```
-//! let _: spi:Spi<_, _, u8> = dp.SPI1.spi((sck, spi::NoMiso, mosi), spi::MODE_0, 1.mhz(), ccdr.peripheral.SPI1, &ccdr.clocks);
+//! let _: spi:Spi<_, _, u8> = dp.SPI1.spi((sck, spi::NoMiso, mosi), spi::MODE_0, 1.mhz(), ccdr.peripheral.SPI1, &ccdr.clocks)?;
 //! ```
 //!
 //! ## Clocks
@@ -61,12 +122,18 @@ pub use crate::hal::spi::{
 use crate::stm32;
 use crate::stm32::rcc::{d2ccip1r, d3ccipr};
 use crate::stm32::spi1::cfg1::MBR_A as MBR;
+use crate::stm32::spi1::cfg2::COMM_A as COMM;
+use crate::stm32::spi1::sr;
 use core::convert::From;
 use core::marker::PhantomData;
-use core::ptr;
+use core::{cmp, mem, ptr};
 use nb;
 use stm32h7::Variant::Val;
 
+/// Maximum number of words in a single transaction, as programmed
+/// into TSIZE
+const MAX_TSIZE: usize = 0xFFFF;
+
 use crate::stm32::{SPI1, SPI2, SPI3, SPI4, SPI5, SPI6};
 
 use crate::gpio::gpioa::{PA12, PA5, PA6, PA7, PA9};
@@ -95,6 +162,9 @@ pub enum Error {
     ModeFault,
     /// CRC error
     Crc,
+    /// The operation is not possible in the configured communication
+    /// mode, or the configuration is not supported by this SPI
+    InvalidMode,
     #[doc(hidden)]
     _Extensible,
 }
@@ -112,6 +182,29 @@ where
 {
 }
 
+/// Specifies the communication mode of the SPI interface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CommunicationMode {
+    /// Full-duplex using separate MISO and MOSI lines
+    FullDuplex,
+    /// Half-duplex on a single bidirectional data line. In master mode
+    /// the MOSI pin is used
+    HalfDuplex,
+    /// Simplex transmit only. The MISO pin is not used
+    Transmitter,
+    /// Simplex receive only. The MOSI pin is not used
+    Receiver,
+}
+
+/// Specifies the frame format of the SPI interface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FrameFormat {
+    /// Motorola SPI frames
+    Motorola,
+    /// TI synchronous serial frames
+    Ti,
+}
+
 /// A structure for specifying SPI configuration.
 ///
 /// This structure uses builder semantics to generate the configuration.
@@ -123,12 +216,17 @@ where
 /// let config = Config::new(Mode::MODE_0)
 ///     .manage_cs()
 /// ```
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct Config {
     mode: Mode,
     swap_miso_mosi: bool,
-    cs_delay: f32,
+    cs_delay: u8,
+    inter_word_delay: u8,
     managed_cs: bool,
+    communication_mode: CommunicationMode,
+    crc: Option<(u32, u8)>,
+    frame_format: FrameFormat,
+    lsb_first: bool,
 }
 
 impl Config {
@@ -140,8 +238,13 @@ impl Config {
         Config {
             mode: mode,
             swap_miso_mosi: false,
-            cs_delay: 0.0,
+            cs_delay: 0,
+            inter_word_delay: 0,
             managed_cs: false,
+            communication_mode: CommunicationMode::FullDuplex,
+            crc: None,
+            frame_format: FrameFormat::Motorola,
+            lsb_first: false,
         }
     }
 
@@ -149,7 +252,7 @@ impl Config {
     ///
     /// Note:
     /// * This function updates the HAL peripheral to treat the pin provided in the MISO parameter
-    /// as the MOSI pin and the pin provided in the MOSI parameter as the MISO pin.
+    ///   as the MOSI pin and the pin provided in the MOSI parameter as the MISO pin.
     pub fn swap_mosi_miso(mut self) -> Self {
         self.swap_miso_mosi = true;
         self
@@ -159,21 +262,95 @@ impl Config {
     ///
     /// Note:
     /// * This function introduces a delay on SCK from the initiation of the transaction. The delay
-    /// is specified as a number of SCK cycles, so the actual delay may vary.
+    ///   is specified in SCK cycles, and is not applied in TI frame format.
     ///
     /// Arguments:
-    /// * `delay` - The delay between CS assertion and the start of the transaction in seconds.
-    /// register for the output pin.
-    pub fn cs_delay(mut self, delay: f32) -> Self {
-        self.cs_delay = delay;
+    /// * `cycles` - The delay between CS assertion and the start of the transaction, from 0 to
+    ///   15 SCK cycles.
+    pub fn cs_delay(mut self, cycles: u8) -> Self {
+        assert!(cycles <= 0xF);
+        self.cs_delay = cycles;
+        self
+    }
+
+    /// Specify a minimum idle time between consecutive data frames.
+    ///
+    /// Note:
+    /// * When CS is managed by the peripheral, CS is also de-asserted between frames in TI
+    ///   frame format.
+    ///
+    /// Arguments:
+    /// * `cycles` - The delay inserted between frames, from 0 to 15 SCK cycles.
+    pub fn inter_word_delay(mut self, cycles: u8) -> Self {
+        assert!(cycles <= 0xF);
+        self.inter_word_delay = cycles;
         self
     }
 
     /// CS pin is automatically managed by the SPI peripheral.
+    ///
+    /// Note:
+    /// * CS is asserted for the whole of each blocking `Transfer` or `Write`. When using the
+    ///   non-blocking `FullDuplex` implementation, CS remains asserted once the first word has
+    ///   been sent.
     pub fn manage_cs(mut self) -> Self {
         self.managed_cs = true;
         self
     }
+
+    /// Select the communication mode of the SPI interface.
+    ///
+    /// Note:
+    /// * In half-duplex mode the data line is driven for writes, and
+    ///   switched to an input for the duration of each
+    ///   [`receive`](Spi::receive) call.
+    ///
+    /// Arguments:
+    /// * `mode` - Full-duplex, half-duplex or one of the simplex modes.
+    pub fn communication_mode(mut self, mode: CommunicationMode) -> Self {
+        self.communication_mode = mode;
+        self
+    }
+
+    /// Select the frame format of the SPI interface.
+    ///
+    /// Note:
+    /// * In TI frame format the clock polarity and phase are fixed by the peripheral, so the
+    ///   SPI mode is ignored.
+    pub fn frame_format(mut self, format: FrameFormat) -> Self {
+        self.frame_format = format;
+        self
+    }
+
+    /// Send and receive each frame starting with the least significant bit.
+    pub fn lsb_first(mut self) -> Self {
+        self.lsb_first = true;
+        self
+    }
+
+    /// Enable hardware CRC calculation and checking.
+    ///
+    /// Note:
+    /// * The CRC is only sent and checked by transactions that
+    ///   program the transfer size into the peripheral: the blocking
+    ///   `Transfer` and `Write` implementations, and
+    ///   [`receive`](Spi::receive). The non-blocking `FullDuplex`
+    ///   implementation never sends a CRC.
+    /// * The CRC length must be equal to, or a multiple of, the word
+    ///   size: 8, 16, 24 or 32 bits for `u8` words, and 16 or 32 bits
+    ///   for `u16` words. SPI4, SPI5 and SPI6 support CRC lengths of at
+    ///   most 16 bits. Other lengths are refused with
+    ///   [`Error::InvalidMode`] when the SPI is configured.
+    ///
+    /// Arguments:
+    /// * `polynomial` - The CRC polynomial, without the implicit most
+    ///   significant term. For example `0x07` for x^8 + x^2 + x + 1.
+    /// * `length` - The CRC length in bits, from 4 to 32.
+    pub fn crc(mut self, polynomial: u32, length: u8) -> Self {
+        assert!((4..=32).contains(&length));
+        self.crc = Some((polynomial, length));
+        self
+    }
 }
 
 impl From<Mode> for Config {
@@ -348,7 +525,7 @@ pub trait SpiExt<SPI, WORD>: Sized {
         freq: T,
         prec: Self::Rec,
         clocks: &CoreClocks,
-    ) -> Spi<SPI, WORD>
+    ) -> Result<Spi<SPI, WORD>, Error>
     where
         PINS: Pins<SPI>,
         T: Into<Hertz>,
@@ -360,7 +537,7 @@ pub trait SpiExt<SPI, WORD>: Sized {
         freq: T,
         prec: Self::Rec,
         clocks: &CoreClocks,
-    ) -> Spi<SPI, WORD>
+    ) -> Result<Spi<SPI, WORD>, Error>
     where
         T: Into<Hertz>,
         CONFIG: Into<Config>;
@@ -379,19 +556,22 @@ macro_rules! spi {
                 .bits(16 - 1) // 16 bit frames
         });
     };
-	($($SPIX:ident: ($spiX:ident, $Rec:ident, $pclkX:ident)
+	($($SPIX:ident: ($spiX:ident, $Rec:ident, $pclkX:ident, $crcmax:expr, $fifo:expr)
        => ($($TY:ident),+),)+) => {
 	    $(
             // For each $TY
             $(
                 impl Spi<$SPIX, $TY> {
+                    /// Number of words in each 32-bit FIFO data packet
+                    const PACKET: usize = 4 / mem::size_of::<$TY>();
+
                     pub fn $spiX<T, CONFIG>(
                         spi: $SPIX,
                         config: CONFIG,
                         freq: T,
                         prec: rec::$Rec,
                         clocks: &CoreClocks,
-                    ) -> Self
+                    ) -> Result<Self, Error>
                     where
                         T: Into<Hertz>,
                         CONFIG: Into<Config>,
@@ -399,11 +579,50 @@ macro_rules! spi {
                         // Enable clock for SPI
                         prec.enable();
 
-                        // Disable SS output
-                        spi.cfg2.write(|w| w.ssoe().disabled());
+                        let mut spi: Self = Spi { spi, _word: PhantomData };
+                        spi.reconfigure(config, freq, clocks)?;
+                        Ok(spi)
+                    }
 
+                    /// Reconfigure the SPI with a new configuration and
+                    /// bitrate.
+                    ///
+                    /// The SPI is disabled whilst it is reconfigured, so
+                    /// any ongoing transfer is aborted.
+                    ///
+                    /// Returns [`Error::InvalidMode`](Error::InvalidMode)
+                    /// without changing the configuration if the CRC
+                    /// length is not supported by this SPI with this
+                    /// word size.
+                    pub fn reconfigure<T, CONFIG>(
+                        &mut self,
+                        config: CONFIG,
+                        freq: T,
+                        clocks: &CoreClocks,
+                    ) -> Result<(), Error>
+                    where
+                        T: Into<Hertz>,
+                        CONFIG: Into<Config>,
+                    {
+                        let spi = &self.spi;
                         let config: Config = config.into();
 
+                        // The CRC length must be a multiple of the word
+                        // size, and no longer than this SPI supports
+                        if let Some((_, length)) = config.crc {
+                            let word = 8 * mem::size_of::<$TY>() as u8;
+                            if length % word != 0 || length > $crcmax {
+                                return Err(Error::InvalidMode);
+                            }
+                        }
+
+                        // The configuration registers may only be
+                        // written whilst the SPI is disabled
+                        spi.cr1.modify(|_, w| w.spe().disabled());
+
+                        // Disable SS output
+                        spi.cfg2.write(|w| w.ssoe().disabled());
+
                         let spi_freq = freq.into().0;
 	                    let spi_ker_ck = match Self::kernel_clk(clocks) {
                             Some(ker_hz) => ker_hz.0,
@@ -426,33 +645,53 @@ macro_rules! spi {
                         });
                         spi!(DSIZE, spi, $TY); // modify CFG1 for DSIZE
 
-                        // ssi: select slave = master mode
-                        spi.cr1.write(|w| w.ssi().slave_not_selected());
-
-                        // Calculate the CS->transaction cycle delay bits.
-                        let cycle_delay: u8 = {
-                            let mut delay: u32 = (config.cs_delay * spi_freq as f32) as u32;
-
-                            // If the cs-delay is specified as non-zero, add 1 to the delay cycles
-                            // before truncation to an integer to ensure that we have at least as
-                            // many cycles as required.
-                            if config.cs_delay > 0.0_f32 {
-                                delay = delay + 1;
+                        // CRC. When the CRC length is the maximum
+                        // supported by this SPI, the most significant
+                        // term of the polynomial does not fit in CRCPOLY
+                        // and is enabled by CRC33_17 instead
+                        let mut crc33_17 = false;
+                        if let Some((polynomial, length)) = config.crc {
+                            spi.cfg1.modify(|_, w| {
+                                w.crcen()
+                                    .enabled()
+                                    .crcsize()
+                                    .bits(length - 1)
+                            });
+                            if length == $crcmax {
+                                crc33_17 = true;
+                                spi.crcpoly.write(|w| w.crcpoly().bits(polynomial));
+                            } else {
+                                spi.crcpoly.write(|w| {
+                                    w.crcpoly().bits(polynomial | (1 << length))
+                                });
                             }
+                        } else {
+                            spi.cfg1.modify(|_, w| w.crcen().disabled());
+                        }
 
-                            if delay > 0xF {
-                                delay = 0xF;
-                            }
+                        // ssi: select slave = master mode
+                        // hddir: transmit, unless receiving in half-duplex mode
+                        spi.cr1.write(|w| {
+                            w.ssi()
+                                .slave_not_selected()
+                                .hddir()
+                                .transmitter()
+                                .crc33_17()
+                                .bit(crc33_17)
+                        });
 
-                            delay as u8
+                        let comm = match config.communication_mode {
+                            CommunicationMode::FullDuplex => COMM::FULLDUPLEX,
+                            CommunicationMode::HalfDuplex => COMM::HALFDUPLEX,
+                            CommunicationMode::Transmitter => COMM::TRANSMITTER,
+                            CommunicationMode::Receiver => COMM::RECEIVER,
                         };
 
-                        // The calculated cycle delay may not be more than 4 bits wide for the
-                        // configuration register.
-
                         // mstr: master configuration
-                        // lsbfrst: MSB first
-                        // comm: full-duplex
+                        // ssoe, ssom: CS output held for the whole transaction
+                        // afcntr: keep driving pins whilst disabled
+                        // mssi: CS->transaction delay, in SCK cycles
+                        // midi: inter-data idleness, in SCK cycles
                         spi.cfg2.write(|w| {
                             w.cpha()
                                 .bit(config.mode.phase ==
@@ -462,21 +701,42 @@ macro_rules! spi {
                                 .master()
                                 .master()
                                 .lsbfrst()
-                                .msbfirst()
+                                .bit(config.lsb_first)
                                 .ssm()
                                 .bit(config.managed_cs == false)
+                                .ssoe()
+                                .bit(config.managed_cs)
+                                .ssom()
+                                .asserted()
+                                .afcntr()
+                                .controlled()
                                 .mssi()
-                                .bits(cycle_delay)
+                                .bits(config.cs_delay)
+                                .midi()
+                                .bits(config.inter_word_delay)
                                 .ioswp()
                                 .bit(config.swap_miso_mosi == true)
                                 .comm()
-                                .full_duplex()
+                                .variant(comm)
                         });
+                        match config.frame_format {
+                            FrameFormat::Motorola => spi.cfg2.modify(|_, w| w.sp().motorola()),
+                            FrameFormat::Ti => spi.cfg2.modify(|_, w| w.sp().ti()),
+                        }
 
                         // spe: enable the SPI bus
-                        spi.cr1.write(|w| w.ssi().slave_not_selected().spe().enabled());
+                        spi.cr1.write(|w| {
+                            w.ssi()
+                                .slave_not_selected()
+                                .hddir()
+                                .transmitter()
+                                .crc33_17()
+                                .bit(crc33_17)
+                                .spe()
+                                .enabled()
+                        });
 
-                        Spi { spi, _word: PhantomData }
+                        Ok(())
                     }
 
                     /// Enable interrupts for the given `event`:
@@ -551,9 +811,253 @@ macro_rules! spi {
                         self.spi.sr.read().ovr().is_overrun()
                     }
 
+                    /// Receive `words.len()` words from a half-duplex or
+                    /// simplex receive only bus.
+                    ///
+                    /// The transfer size is programmed into the
+                    /// peripheral, so that exactly `words.len()` frames
+                    /// are clocked. In half-duplex mode the data line is
+                    /// returned to the transmit direction afterwards.
+                    ///
+                    /// Returns [`Error::InvalidMode`](Error::InvalidMode)
+                    /// if the SPI is configured for full-duplex or
+                    /// transmit only communication.
+                    pub fn receive(&mut self, words: &mut [$TY]) -> Result<(), Error> {
+                        let comm = self.spi.cfg2.read().comm().variant();
+                        if comm != COMM::HALFDUPLEX && comm != COMM::RECEIVER {
+                            return Err(Error::InvalidMode);
+                        }
+
+                        for chunk in words.chunks_mut(MAX_TSIZE) {
+                            self.transaction(chunk.len() as u16, true, |spi| {
+                                spi.read_words(chunk)
+                            })?;
+                        }
+
+                        Ok(())
+                    }
+
                     pub fn free(self) -> ($SPIX, rec::$Rec) {
                         (self.spi, rec::$Rec { _marker: PhantomData })
                     }
+
+                    /// Set the hardware transfer size, and the
+                    /// direction of the data line in half-duplex
+                    /// mode. A transfer size of zero selects endless
+                    /// transfers.
+                    ///
+                    /// TSIZE and HDDIR may only be changed whilst the SPI
+                    /// is disabled, so any ongoing transfer is aborted.
+                    fn setup_transfer(&mut self, size: u16, receive: bool) {
+                        self.spi.cr1.modify(|_, w| w.spe().disabled());
+                        self.spi.ifcr.write(|w| w.eotc().clear().txtfc().clear());
+                        self.spi.cr2.write(|w| w.tsize().bits(size));
+
+                        // fthlv: transfers of a programmed size move
+                        // whole packets through the FIFO. Endless
+                        // transfers, as used by the non-blocking
+                        // `FullDuplex` implementation, move single words
+                        let fthlv = if size == 0 { 0 } else { Self::PACKET as u8 - 1 };
+                        self.spi.cfg1.modify(|_, w| w.fthlv().bits(fthlv));
+
+                        if receive {
+                            self.spi.cr1.modify(|_, w| w.hddir().receiver());
+                        } else {
+                            self.spi.cr1.modify(|_, w| w.hddir().transmitter());
+                        }
+                        self.spi.cr1.modify(|_, w| w.spe().enabled());
+                    }
+
+                    /// Perform a transaction of exactly `size` frames,
+                    /// moving data with `f`.
+                    ///
+                    /// A CRC, if enabled, is appended and checked by
+                    /// the hardware at the end of the transaction. A
+                    /// hardware managed CS stays asserted for the whole
+                    /// transaction.
+                    fn transaction<F>(&mut self, size: u16, receive: bool, f: F) -> Result<(), Error>
+                    where
+                        F: FnOnce(&mut Self) -> Result<(), Error>,
+                    {
+                        self.setup_transfer(size, receive);
+                        self.spi.cr1.modify(|_, w| w.cstart().started());
+
+                        let result = f(self).and_then(|_| self.end_transfer());
+
+                        if result.is_err() {
+                            // Clear the error flags, otherwise they would
+                            // fail every later transaction
+                            self.spi.ifcr.write(|w| w.ovrc().clear().modfc().clear());
+                        }
+
+                        // Return to endless transfers in the transmit
+                        // direction
+                        self.setup_transfer(0, false);
+
+                        result
+                    }
+
+                    /// Wait for the end of a transfer with a
+                    /// programmed size, and check the received CRC
+                    fn end_transfer(&mut self) -> Result<(), Error> {
+                        // Check for errors whilst waiting. A mode fault
+                        // disables the SPI, so EOT would never be set
+                        let result = loop {
+                            let sr = match self.status() {
+                                Ok(sr) => sr,
+                                Err(e) => break Err(e),
+                            };
+
+                            if sr.crce().is_error() {
+                                break Err(Error::Crc);
+                            } else if sr.eot().is_completed() {
+                                break Ok(());
+                            }
+                        };
+                        self.spi.ifcr.write(|w| {
+                            w.eotc().clear().txtfc().clear().crcec().clear()
+                        });
+
+                        result
+                    }
+
+                    /// Read the status register, returning an error if
+                    /// an overrun or mode fault has occurred
+                    fn status(&self) -> Result<sr::R, Error> {
+                        let sr = self.spi.sr.read();
+
+                        if sr.ovr().is_overrun() {
+                            Err(Error::Overrun)
+                        } else if sr.modf().is_fault() {
+                            Err(Error::ModeFault)
+                        } else {
+                            Ok(sr)
+                        }
+                    }
+
+                    fn write_txdr(&mut self, word: $TY) {
+                        // NOTE(write_volatile) write only 1 word (the
+                        // svd2rust API only allows writing a word)
+                        unsafe {
+                            ptr::write_volatile(
+                                &self.spi.txdr as *const _ as *mut $TY,
+                                word,
+                            )
+                        }
+                    }
+
+                    fn read_rxdr(&mut self) -> $TY {
+                        // NOTE(read_volatile) read only 1 word (the
+                        // svd2rust API only allows reading a word)
+                        unsafe {
+                            ptr::read_volatile(
+                                &self.spi.rxdr as *const _ as *const $TY,
+                            )
+                        }
+                    }
+
+                    /// Write a packet of words to the TX FIFO, or a
+                    /// single word if fewer than a packet are left.
+                    /// Returns the number of words written
+                    fn write_tx(&mut self, words: &[$TY]) -> usize {
+                        if words.len() >= Self::PACKET {
+                            // The first word is sent from the least
+                            // significant bits
+                            let packet = words[..Self::PACKET]
+                                .iter()
+                                .rev()
+                                .fold(0, |packet, &word| {
+                                    packet << (8 * mem::size_of::<$TY>()) | u32::from(word)
+                                });
+                            self.spi.txdr.write(|w| w.txdr().bits(packet));
+                            Self::PACKET
+                        } else {
+                            self.write_txdr(words[0]);
+                            1
+                        }
+                    }
+
+                    /// Read a packet of words from the RX FIFO, or a
+                    /// single word if fewer than a packet are left.
+                    /// Returns the number of words read, which is zero
+                    /// if none are available yet
+                    fn read_rx(&mut self, sr: &sr::R, words: &mut [$TY]) -> usize {
+                        if words.len() >= Self::PACKET {
+                            if sr.rxp().is_not_empty() {
+                                let packet = self.spi.rxdr.read().rxdr().bits();
+                                for (i, word) in words[..Self::PACKET].iter_mut().enumerate() {
+                                    *word = (packet >> (8 * mem::size_of::<$TY>() * i)) as $TY;
+                                }
+                                return Self::PACKET;
+                            }
+                        } else if sr.rxplvl().bits() != 0 {
+                            // RXP is only set for a whole packet, so the
+                            // last words are counted by RXPLVL
+                            words[0] = self.read_rxdr();
+                            return 1;
+                        }
+                        0
+                    }
+
+                    /// Send each word, replacing it with the word
+                    /// received.
+                    ///
+                    /// The TX FIFO is filled ahead of the words
+                    /// received, up to the FIFO depth so that the RX
+                    /// FIFO cannot overrun.
+                    fn exchange_words(&mut self, words: &mut [$TY]) -> Result<(), Error> {
+                        let fifo_words = $fifo / mem::size_of::<$TY>();
+                        let (mut tx, mut rx) = (0, 0);
+
+                        while rx < words.len() {
+                            let sr = self.status()?;
+
+                            if tx < words.len()
+                                && tx - rx + Self::PACKET <= fifo_words
+                                && sr.txp().is_not_full()
+                            {
+                                tx += self.write_tx(&words[tx..]);
+                            }
+                            rx += self.read_rx(&sr, &mut words[rx..]);
+                        }
+                        Ok(())
+                    }
+
+                    /// Send each word. If `full_duplex`, also read and
+                    /// discard the words received.
+                    fn write_words(&mut self, words: &[$TY], full_duplex: bool) -> Result<(), Error> {
+                        let fifo_words = $fifo / mem::size_of::<$TY>();
+                        let rx_len = if full_duplex { words.len() } else { 0 };
+                        let mut discard = [0; 4];
+                        let (mut tx, mut rx) = (0, 0);
+
+                        while tx < words.len() || rx < rx_len {
+                            let sr = self.status()?;
+
+                            if tx < words.len()
+                                && (!full_duplex || tx - rx + Self::PACKET <= fifo_words)
+                                && sr.txp().is_not_full()
+                            {
+                                tx += self.write_tx(&words[tx..]);
+                            }
+                            if rx < rx_len {
+                                let n = cmp::min(rx_len - rx, Self::PACKET);
+                                rx += self.read_rx(&sr, &mut discard[..n]);
+                            }
+                        }
+                        Ok(())
+                    }
+
+                    /// Read words without sending
+                    fn read_words(&mut self, words: &mut [$TY]) -> Result<(), Error> {
+                        let mut rx = 0;
+
+                        while rx < words.len() {
+                            let sr = self.status()?;
+                            rx += self.read_rx(&sr, &mut words[rx..]);
+                        }
+                        Ok(())
+                    }
                 }
 
                 impl SpiExt<$SPIX, $TY> for $SPIX {
@@ -564,7 +1068,7 @@ macro_rules! spi {
                                     config: CONFIG,
                                     freq: T,
                                     prec: rec::$Rec,
-                                    clocks: &CoreClocks) -> Spi<$SPIX, $TY>
+                                    clocks: &CoreClocks) -> Result<Spi<$SPIX, $TY>, Error>
 	                where
 	                    PINS: Pins<$SPIX>,
 	                    T: Into<Hertz>,
@@ -577,7 +1081,7 @@ macro_rules! spi {
                                         config: CONFIG,
                                         freq: T,
                                         prec: rec::$Rec,
-                                        clocks: &CoreClocks) -> Spi<$SPIX, $TY>
+                                        clocks: &CoreClocks) -> Result<Spi<$SPIX, $TY>, Error>
 	                where
 	                    T: Into<Hertz>,
                         CONFIG: Into<Config>,
@@ -640,11 +1144,43 @@ macro_rules! spi {
                     }
                 }
 
-                impl hal::blocking::spi::transfer::Default<$TY>
-                    for Spi<$SPIX, $TY> {}
+                impl hal::blocking::spi::Transfer<$TY> for Spi<$SPIX, $TY> {
+                    type Error = Error;
+
+                    fn transfer<'w>(&mut self, words: &'w mut [$TY]) -> Result<&'w [$TY], Error> {
+                        if !self.spi.cfg2.read().comm().is_full_duplex() {
+                            return Err(Error::InvalidMode);
+                        }
+
+                        for chunk in words.chunks_mut(MAX_TSIZE) {
+                            self.transaction(chunk.len() as u16, false, |spi| {
+                                spi.exchange_words(chunk)
+                            })?;
+                        }
+
+                        Ok(words)
+                    }
+                }
+
+                impl hal::blocking::spi::Write<$TY> for Spi<$SPIX, $TY> {
+                    type Error = Error;
+
+                    fn write(&mut self, words: &[$TY]) -> Result<(), Error> {
+                        let full_duplex = match self.spi.cfg2.read().comm().variant() {
+                            COMM::FULLDUPLEX => true,
+                            COMM::HALFDUPLEX | COMM::TRANSMITTER => false,
+                            COMM::RECEIVER => return Err(Error::InvalidMode),
+                        };
+
+                        for chunk in words.chunks(MAX_TSIZE) {
+                            self.transaction(chunk.len() as u16, false, |spi| {
+                                spi.write_words(chunk, full_duplex)
+                            })?;
+                        }
 
-                impl hal::blocking::spi::write::Default<$TY>
-                    for Spi<$SPIX, $TY> {}
+                        Ok(())
+                    }
+                }
             )+
         )+
 	}
@@ -720,13 +1256,14 @@ macro_rules! spi6sel {
     }
 }
 
+// (constructor, rec, pclk, maximum CRC length, FIFO size in bytes)
 spi! {
-    SPI1: (spi1, Spi1, pclk2) => (u8, u16),
-    SPI2: (spi2, Spi2, pclk1) => (u8, u16),
-    SPI3: (spi3, Spi3, pclk1) => (u8, u16),
-    SPI4: (spi4, Spi4, pclk2) => (u8, u16),
-    SPI5: (spi5, Spi5, pclk2) => (u8, u16),
-    SPI6: (spi6, Spi6, pclk2) => (u8, u16),
+    SPI1: (spi1, Spi1, pclk2, 32, 16) => (u8, u16),
+    SPI2: (spi2, Spi2, pclk1, 32, 16) => (u8, u16),
+    SPI3: (spi3, Spi3, pclk1, 32, 16) => (u8, u16),
+    SPI4: (spi4, Spi4, pclk2, 16, 8) => (u8, u16),
+    SPI5: (spi5, Spi5, pclk2, 16, 8) => (u8, u16),
+    SPI6: (spi6, Spi6, pclk2, 16, 8) => (u8, u16),
 }
 
 spi123sel! {
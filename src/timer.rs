@@ -299,6 +299,34 @@ macro_rules! hal {
     }
 }
 
+macro_rules! master_mode {
+    ($($TIMX:ident: |$w:ident| $mms_update:expr,)+) => {
+        $(
+            impl Timer<$TIMX> {
+                /// Pulse the trigger output (TRGO) on each update event,
+                /// so that the timer can trigger other peripherals such
+                /// as the ADC
+                pub fn trigger_on_update(&mut self) {
+                    self.tim.cr2.modify(|_, $w| $mms_update);
+                }
+            }
+        )+
+    }
+}
+
+master_mode! {
+    TIM1: |w| w.mms().update(),
+    TIM8: |w| w.mms().update(),
+    TIM2: |w| w.mms().update(),
+    TIM3: |w| w.mms().update(),
+    TIM4: |w| w.mms().update(),
+    TIM5: |w| w.mms().update(),
+    TIM6: |w| w.mms().update(),
+    TIM7: |w| w.mms().update(),
+    // MMS = 0b010: Update
+    TIM15: |w| unsafe { w.mms().bits(0b010) },
+}
+
 hal! {
     // Advanced-control
     TIM1: (tim1, Tim1),